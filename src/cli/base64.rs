@@ -1,4 +1,4 @@
-use crate::{get_reader, process_decode, process_encode, CmdExector};
+use crate::{CmdExector, Codec, DecodeOpts, EncodeOpts};
 
 use super::verify_file;
 use anyhow::Ok;
@@ -21,8 +21,20 @@ pub struct Base64EncodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-", help = "input file path")]
     pub input: String,
 
-    #[arg(long, value_parser = parse_base64_format, default_value = "standard", help = "base64 format")]
+    #[arg(long, value_parser = parse_base64_encode_format, default_value = "standard", help = "base64 format: standard, standard-nopad, urlsafe, urlsafe-pad, mime, bcrypt or crypt")]
     pub format: Base64Format,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    // 只写 --wrap 时按 MIME 的规定每行 76 个字符
+    #[arg(long, num_args = 0..=1, default_missing_value = "76", value_parser = clap::value_parser!(u32).range(1..), help = "wrap encoded lines after this many characters, 76 if no value is given")]
+    pub wrap: Option<u32>,
 }
 
 #[derive(Debug, Parser)]
@@ -30,27 +42,58 @@ pub struct Base64DecodeOpts {
     #[arg(short, long, value_parser = verify_file, default_value = "-", help = "input file path")]
     pub input: String,
 
-    #[arg(long, value_parser = parse_base64_format, default_value = "standard", help = "base64 format")]
+    #[arg(long, value_parser = parse_base64_format, default_value = "standard", help = "base64 format: standard, standard-nopad, urlsafe, urlsafe-pad, mime, bcrypt, crypt or auto")]
     pub format: Base64Format,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
 }
 
-#[derive(Debug, Clone, Copy)]
+// Standard 和 UrlSafe 沿用原来的默认：前者带填充，后者不带填充
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Base64Format {
     Standard,
+    StandardNoPad,
     UrlSafe,
+    UrlSafePad,
+    // 标准字母表、带填充，编码时每 76 个字符以 CRLF 换行
+    Mime,
+    Bcrypt,
+    Crypt,
+    // 只用于解码，根据输入推测字母表和填充
+    Auto,
 }
 
 pub fn parse_base64_format(format: &str) -> Result<Base64Format, anyhow::Error> {
     format.parse()
 }
 
+// 编码时必须明确格式
+pub fn parse_base64_encode_format(format: &str) -> Result<Base64Format, anyhow::Error> {
+    match format.parse()? {
+        Base64Format::Auto => Err(anyhow::anyhow!("auto is only supported for decode")),
+        format => Ok(format),
+    }
+}
+
 impl FromStr for Base64Format {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "standard" => Ok(Base64Format::Standard),
+            "standard-nopad" => Ok(Base64Format::StandardNoPad),
             "urlsafe" => Ok(Base64Format::UrlSafe),
+            "urlsafe-pad" => Ok(Base64Format::UrlSafePad),
+            "mime" => Ok(Base64Format::Mime),
+            "bcrypt" => Ok(Base64Format::Bcrypt),
+            "crypt" => Ok(Base64Format::Crypt),
+            "auto" => Ok(Base64Format::Auto),
             _ => Err(anyhow::anyhow!("invalid base64 format")),
         }
     }
@@ -60,7 +103,13 @@ impl From<Base64Format> for &'static str {
     fn from(format: Base64Format) -> Self {
         match format {
             Base64Format::Standard => "standard",
+            Base64Format::StandardNoPad => "standard-nopad",
             Base64Format::UrlSafe => "urlsafe",
+            Base64Format::UrlSafePad => "urlsafe-pad",
+            Base64Format::Mime => "mime",
+            Base64Format::Bcrypt => "bcrypt",
+            Base64Format::Crypt => "crypt",
+            Base64Format::Auto => "auto",
         }
     }
 }
@@ -71,20 +120,28 @@ impl fmt::Display for Base64Format {
     }
 }
 
+// rcli base64 是 rcli encode/decode --codec base64 的别名
 impl CmdExector for Base64EncodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = get_reader(&self.input)?;
-        let ret = process_encode(&mut reader, self.format)?;
-        println!("{}", ret);
-        Ok(())
+        let opts = EncodeOpts {
+            input: self.input,
+            output: self.output,
+            codec: Codec::Base64,
+            format: Some(self.format),
+            wrap: self.wrap,
+        };
+        opts.execute().await
     }
 }
 
 impl CmdExector for Base64DecodeOpts {
     async fn execute(self) -> anyhow::Result<()> {
-        let mut reader = get_reader(&self.input)?;
-        let ret = process_decode(&mut reader, self.format)?;
-        println!("{}", ret);
-        Ok(())
+        let opts = DecodeOpts {
+            input: self.input,
+            output: self.output,
+            codec: Codec::Base64,
+            format: Some(self.format),
+        };
+        opts.execute().await
     }
 }
@@ -0,0 +1,171 @@
+use std::{fmt, io::Write, str::FromStr};
+
+use clap::Parser;
+
+use super::{parse_base64_encode_format, parse_base64_format, verify_file};
+use crate::{
+    get_reader, get_writer, process_codec_decode, process_codec_encode, Base64Format, CmdExector,
+};
+
+#[derive(Debug, Parser)]
+pub struct EncodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "input file path")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_codec, default_value = "base64", help = "codec: hex, xxd, base32, base32-crockford, base58, base64, ascii85 or z85")]
+    pub codec: Codec,
+
+    #[arg(long, value_parser = parse_base64_encode_format, help = "base64 format: standard, standard-nopad, urlsafe, urlsafe-pad, mime, bcrypt or crypt, defaults to standard")]
+    pub format: Option<Base64Format>,
+
+    #[arg(long, num_args = 0..=1, default_missing_value = "76", value_parser = clap::value_parser!(u32).range(1..), help = "wrap encoded lines after this many characters, 76 if no value is given")]
+    pub wrap: Option<u32>,
+}
+
+#[derive(Debug, Parser)]
+pub struct DecodeOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "input file path")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_codec, default_value = "base64", help = "codec: hex, xxd, base32, base32-crockford, base58, base64, ascii85 or z85")]
+    pub codec: Codec,
+
+    #[arg(long, value_parser = parse_base64_format, help = "base64 format: standard, standard-nopad, urlsafe, urlsafe-pad, mime, bcrypt, crypt or auto, defaults to standard")]
+    pub format: Option<Base64Format>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Hex,
+    // xxd 风格的十六进制转储
+    Xxd,
+    Base32,
+    Base32Crockford,
+    // 比特币的字母表
+    Base58,
+    Base64,
+    Ascii85,
+    Z85,
+}
+
+impl CmdExector for EncodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = base64_format(self.codec, self.format)?;
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        let wrap = self.wrap.map(|w| w as usize);
+        process_codec_encode(&mut reader, &mut writer, self.codec, format, wrap)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl CmdExector for DecodeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = base64_format(self.codec, self.format)?;
+        let mut reader = get_reader(&self.input)?;
+        // 解码后的数据原样输出，不追加换行
+        let mut writer = get_writer(&self.output)?;
+        let detected = process_codec_decode(&mut reader, &mut writer, self.codec, format)?;
+        if let Some(detected) = detected.filter(|_| format == Base64Format::Auto) {
+            eprintln!("detected base64 format: {}", detected);
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+// --format 只对 base64 有效，其它编码指定时直接报错
+fn base64_format(codec: Codec, format: Option<Base64Format>) -> anyhow::Result<Base64Format> {
+    match (codec, format) {
+        (Codec::Base64, format) => Ok(format.unwrap_or(Base64Format::Standard)),
+        (_, None) => Ok(Base64Format::Standard),
+        (codec, Some(_)) => Err(anyhow::anyhow!(
+            "--format only applies to base64, not {}",
+            codec
+        )),
+    }
+}
+
+pub fn parse_codec(codec: &str) -> Result<Codec, anyhow::Error> {
+    codec.parse()
+}
+
+impl FromStr for Codec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Codec::Hex),
+            "xxd" => Ok(Codec::Xxd),
+            "base32" => Ok(Codec::Base32),
+            "base32-crockford" => Ok(Codec::Base32Crockford),
+            "base58" => Ok(Codec::Base58),
+            "base64" => Ok(Codec::Base64),
+            "ascii85" => Ok(Codec::Ascii85),
+            "z85" => Ok(Codec::Z85),
+            _ => Err(anyhow::anyhow!("invalid codec")),
+        }
+    }
+}
+
+impl From<Codec> for &'static str {
+    fn from(codec: Codec) -> Self {
+        match codec {
+            Codec::Hex => "hex",
+            Codec::Xxd => "xxd",
+            Codec::Base32 => "base32",
+            Codec::Base32Crockford => "base32-crockford",
+            Codec::Base58 => "base58",
+            Codec::Base64 => "base64",
+            Codec::Ascii85 => "ascii85",
+            Codec::Z85 => "z85",
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_format_only_for_base64() {
+        let opts = EncodeOpts::try_parse_from(["encode", "--codec", "hex"]).unwrap();
+        assert_eq!(
+            base64_format(opts.codec, opts.format).unwrap(),
+            Base64Format::Standard
+        );
+
+        let opts = EncodeOpts::try_parse_from(["encode", "--codec", "hex", "--format", "urlsafe"])
+            .unwrap();
+        assert!(base64_format(opts.codec, opts.format).is_err());
+
+        let opts = DecodeOpts::try_parse_from(["decode", "--format", "auto"]).unwrap();
+        assert_eq!(
+            base64_format(opts.codec, opts.format).unwrap(),
+            Base64Format::Auto
+        );
+    }
+}
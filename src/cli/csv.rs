@@ -1,47 +1,607 @@
-use crate::{process_csv, CmdExector};
+use crate::{
+    get_content, get_reader, get_writer, process_csv, process_csv_dedup, process_csv_merge,
+    process_csv_sort, process_csv_stats, process_csv_validate, process_excel, process_to_csv,
+    write_csv_stats, write_csv_stats_table, CmdExector, CsvReaderConfig, CsvSchema, CsvTransform,
+    CsvValidator, RowFilter, SortKey,
+};
 
 use super::verify_file;
-use clap::Parser;
-use std::{fmt, str::FromStr};
+use clap::{ArgAction, Parser};
+use enum_dispatch::enum_dispatch;
+use std::{fmt, path::Path, str::FromStr};
+
+/// csv validate 的列规则：内置的 Player 结构体，或者列类型声明
+#[derive(Debug, Clone)]
+pub enum ValidateSchema {
+    Player,
+    Columns(CsvSchema),
+}
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
     Yaml,
+    Toml,
+    Ndjson,
+    MessagePack,
+    Parquet,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEncoding {
+    Utf8,
+    Gbk,
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    Excel,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DataFormat {
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FlattenPolicy {
+    Dot,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Outer,
 }
 
 // 1.default_value 和 default_value_t 的区别
 // 2.short 和 long 的区别，以及 header 为什么没有 short
+// 3.没有子命令时执行 csv 转换，子命令和转换参数不能同时使用
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file, help = "csv file path")]
-    pub input: String,
+    #[command(subcommand)]
+    pub command: Option<CsvSubCommand>,
+
+    #[arg(short, long, required = true, value_parser = verify_file, help = "csv or excel file path, - for stdin")]
+    pub input: Option<String>,
 
-    #[arg(short, long, help = "output file path")]
+    #[arg(long, value_parser = parse_input_format, help = "input format: csv or xlsx, detected from the file extension by default")]
+    pub input_format: Option<InputFormat>,
+
+    #[arg(long, help = "excel sheet to read, the first sheet by default")]
+    pub sheet: Option<String>,
+
+    #[arg(short, long, help = "output file path, - for stdout")]
     pub output: Option<String>,
 
-    #[arg(short, long, default_value = "json", value_parser = parse_format,help = "output format,json or yaml")]
+    #[arg(short, long, default_value = "json", value_parser = parse_format,help = "output format: json, yaml, toml, ndjson, msgpack or parquet")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',', help = "csv delimiter")]
-    pub delimiter: char,
+    #[arg(
+        long,
+        help = "infer int/float/bool/null values instead of emitting strings"
+    )]
+    pub infer: bool,
+
+    #[arg(long, value_parser = parse_schema, help = "column types, e.g. \"Kit Number=int,DOB=string\"")]
+    pub schema: Option<CsvSchema>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "columns to output, e.g. Name,Position"
+    )]
+    pub select: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', value_parser = parse_rename, help = "rename columns, e.g. \"Kit Number=kit\"")]
+    pub rename: Vec<(String, String)>,
+
+    #[arg(long = "where", value_parser = parse_row_filter, help = "filter rows with =, !=, <, > or contains, e.g. \"Nationality=Italy\"")]
+    pub filters: Vec<RowFilter>,
+
+    #[arg(long, help = "output a map from each value of this column to its rows")]
+    pub group_by: Option<String>,
+
+    #[arg(
+        long,
+        help = "turn dotted column names like address.city into nested objects"
+    )]
+    pub nest: bool,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum CsvSubCommand {
+    #[command(
+        name = "from-json",
+        about = "convert json or yaml array of objects to csv"
+    )]
+    FromJson(CsvFromJsonOpts),
+    #[command(about = "validate every csv row against a column type spec or a json schema")]
+    Validate(CsvValidateOpts),
+    #[command(about = "report per column statistics of a csv file")]
+    Stats(CsvStatsOpts),
+    #[command(about = "sort csv rows by columns, spilling to temp files for large inputs")]
+    Sort(CsvSortOpts),
+    #[command(about = "remove duplicate csv rows")]
+    Dedup(CsvDedupOpts),
+    #[command(about = "join two csv files on a column")]
+    Merge(CsvMergeOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromJsonOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "json or yaml file path")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "output.csv",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_data_format, help = "input format, json or yaml, detected from the file extension by default")]
+    pub format: Option<DataFormat>,
+
+    #[arg(long, default_value = "dot", value_parser = parse_flatten_policy, help = "how nested values are written: dot (a.b columns) or json (json encoded cell)")]
+    pub flatten: FlattenPolicy,
+
+    #[arg(short, long, default_value = ",", value_parser = parse_csv_byte, help = "csv delimiter")]
+    pub delimiter: u8,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "csv file path, - for stdin")]
+    pub input: String,
+
+    #[arg(long, value_parser = parse_validate_schema, required_unless_present = "json_schema", help = "column types, e.g. \"Kit Number=int,DOB=string\", or player for the built-in Player struct")]
+    pub schema: Option<ValidateSchema>,
+
+    #[arg(long, value_parser = verify_file, help = "json schema file each row is validated against")]
+    pub json_schema: Option<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "csv file path, - for stdin")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, help = "output format, a text table is printed by default")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "number of most frequent values to report"
+    )]
+    pub top: usize,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSortOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "csv file path, - for stdin")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, help = "output format, csv is written by default")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_sort_key, help = "columns to sort by, e.g. \"Position,Kit Number:desc\"")]
+    pub by: Vec<SortKey>,
+
+    #[arg(
+        long,
+        default_value_t = 100_000,
+        help = "rows sorted in memory before spilling to a temp file"
+    )]
+    pub chunk_size: usize,
 
-    #[arg(long, default_value_t = true, help = "csv header")]
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDedupOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "csv file path, - for stdin")]
+    pub input: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, help = "output format, csv is written by default")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "columns identifying a duplicate, the whole row by default"
+    )]
+    pub key: Vec<String>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMergeOpts {
+    #[arg(value_parser = verify_file, help = "left csv file path, - for stdin")]
+    pub left: String,
+
+    #[arg(value_parser = verify_file, help = "right csv file path, loaded into memory")]
+    pub right: String,
+
+    #[arg(long, help = "column both files are joined on")]
+    pub on: String,
+
+    #[arg(long, default_value = "inner", value_parser = parse_join_kind, help = "join type: inner, left or outer")]
+    pub how: JoinKind,
+
+    #[arg(
+        short,
+        long,
+        default_value = "-",
+        help = "output file path, - for stdout"
+    )]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_format, help = "output format, csv is written by default")]
+    pub format: Option<OutputFormat>,
+
+    #[command(flatten)]
+    pub reader: CsvReaderOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvReaderOpts {
+    #[arg(short, long, default_value = ",", value_parser = parse_csv_byte, help = "csv delimiter")]
+    pub delimiter: u8,
+
+    #[arg(long, default_value_t = true, action = ArgAction::Set, help = "csv header, col0..colN will be used when false")]
     pub header: bool,
+
+    #[arg(long, default_value = "\"", value_parser = parse_csv_byte, help = "csv quote char")]
+    pub quote: u8,
+
+    #[arg(long, value_parser = parse_csv_byte, help = "csv escape char, disables double quote escaping")]
+    pub escape: Option<u8>,
+
+    #[arg(long, value_parser = parse_csv_byte, help = "csv lines starting with this char are ignored")]
+    pub comment: Option<u8>,
+
+    #[arg(long, help = "trim whitespace around csv headers and fields")]
+    pub trim: bool,
+
+    #[arg(long, help = "allow csv records with a varying number of fields")]
+    pub flexible: bool,
+
+    #[arg(long, default_value = "utf-8", value_parser = parse_input_encoding, help = "csv input encoding: utf-8, gbk, latin1, utf-16, utf-16be or auto")]
+    pub encoding: InputEncoding,
 }
 
 impl CmdExector for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        if let Some(command) = self.command {
+            return command.execute().await;
+        }
+        let input = self
+            .input
+            .ok_or_else(|| anyhow::anyhow!("csv input file is required"))?;
+
         let output = if let Some(output) = self.output {
             output
         } else {
             format!("output.{}", self.format)
         };
-        process_csv(&self.input, output, self.format)
+        let schema = CsvSchema {
+            infer: self.infer,
+            ..self.schema.unwrap_or_default()
+        };
+        let transform = CsvTransform {
+            select: self.select,
+            rename: self.rename.into_iter().collect(),
+            filters: self.filters,
+            group_by: self.group_by,
+            nest: self.nest,
+        };
+        let input_format = match self.input_format {
+            Some(format) => format,
+            None => match Path::new(&input).extension().and_then(|ext| ext.to_str()) {
+                Some("xlsx" | "xlsm" | "xls" | "ods") => InputFormat::Excel,
+                _ => InputFormat::Csv,
+            },
+        };
+        if self.sheet.is_some() && input_format != InputFormat::Excel {
+            anyhow::bail!("--sheet can only be used with excel input");
+        }
+        let csv_only = self.reader.csv_only_args();
+        if input_format == InputFormat::Excel && !csv_only.is_empty() {
+            anyhow::bail!("{} can only be used with csv input", csv_only.join(", "));
+        }
+        // 在读取输入和创建输出文件之前检查，避免留下被截断的输出文件
+        if transform.group_by.is_some() && matches!(self.format, OutputFormat::Parquet) {
+            anyhow::bail!("parquet output only supports a list of rows");
+        }
+
+        let mut reader = get_reader(&input)?;
+        let mut writer = get_writer(&output)?;
+        match input_format {
+            InputFormat::Csv => process_csv(
+                &mut reader,
+                &mut writer,
+                self.format,
+                &self.reader.into(),
+                &schema,
+                &transform,
+            ),
+            InputFormat::Excel => process_excel(
+                &mut reader,
+                &mut writer,
+                self.format,
+                self.sheet.as_deref(),
+                self.reader.header,
+                &schema,
+                &transform,
+            ),
+        }
+    }
+}
+
+impl CmdExector for CsvFromJsonOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let format = match self.format {
+            Some(format) => format,
+            None => match Path::new(&self.input).extension() {
+                Some(ext) if ext == "yaml" || ext == "yml" => DataFormat::Yaml,
+                _ => DataFormat::Json,
+            },
+        };
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        process_to_csv(
+            &mut reader,
+            &mut writer,
+            format,
+            self.flatten,
+            self.delimiter,
+        )
+    }
+}
+
+impl CmdExector for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut validator = match self.schema {
+            Some(ValidateSchema::Player) => CsvValidator::player(),
+            Some(ValidateSchema::Columns(schema)) => CsvValidator::new(schema),
+            None => CsvValidator::new(CsvSchema::default()),
+        };
+        if let Some(json_schema) = self.json_schema {
+            let schema = serde_json::from_slice(&get_content(&json_schema)?)?;
+            validator = validator.with_json_schema(&schema)?;
+        }
+
+        let mut reader = get_reader(&self.input)?;
+        let report = process_csv_validate(&mut reader, &self.reader.into(), &validator)?;
+        for error in &report.errors {
+            println!("{}", error);
+        }
+
+        match report.is_valid() {
+            true => {
+                eprintln!("✓ {} rows validated", report.rows);
+                Ok(())
+            }
+            false if report.header_errors > 0 => Err(anyhow::anyhow!(
+                "{} header errors, {} of {} rows failed validation",
+                report.header_errors,
+                report.failed_rows,
+                report.rows
+            )),
+            false => Err(anyhow::anyhow!(
+                "{} of {} rows failed validation",
+                report.failed_rows,
+                report.rows
+            )),
+        }
+    }
+}
+
+impl CmdExector for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let stats = process_csv_stats(&mut reader, &self.reader.into(), self.top)?;
+
+        let mut writer = get_writer(&self.output)?;
+        match self.format {
+            Some(format) => write_csv_stats(&mut writer, &stats, format),
+            None => write_csv_stats_table(&mut writer, &stats),
+        }
+    }
+}
+
+impl CmdExector for CsvSortOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        process_csv_sort(
+            &mut reader,
+            &mut writer,
+            self.format,
+            &self.reader.into(),
+            &self.by,
+            self.chunk_size,
+        )
+    }
+}
+
+impl CmdExector for CsvDedupOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let mut writer = get_writer(&self.output)?;
+        process_csv_dedup(
+            &mut reader,
+            &mut writer,
+            self.format,
+            &self.reader.into(),
+            &self.key,
+        )
+    }
+}
+
+impl CmdExector for CsvMergeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if self.left == "-" && self.right == "-" {
+            anyhow::bail!("only one of the inputs can be stdin");
+        }
+        let mut left = get_reader(&self.left)?;
+        let mut right = get_reader(&self.right)?;
+        let mut writer = get_writer(&self.output)?;
+        process_csv_merge(
+            &mut left,
+            &mut right,
+            &mut writer,
+            self.format,
+            &self.reader.into(),
+            &self.on,
+            self.how,
+        )
+    }
+}
+
+impl CsvReaderOpts {
+    // 改过默认值的 csv 专用参数，excel 输入只支持 --header
+    fn csv_only_args(&self) -> Vec<&'static str> {
+        let default = CsvReaderConfig::default();
+        [
+            ("--delimiter", self.delimiter != default.delimiter),
+            ("--quote", self.quote != default.quote),
+            ("--escape", self.escape.is_some()),
+            ("--comment", self.comment.is_some()),
+            ("--trim", self.trim),
+            ("--flexible", self.flexible),
+            ("--encoding", self.encoding != default.encoding),
+        ]
+        .into_iter()
+        .filter_map(|(arg, set)| set.then_some(arg))
+        .collect()
+    }
+}
+
+impl From<CsvReaderOpts> for CsvReaderConfig {
+    fn from(opts: CsvReaderOpts) -> Self {
+        Self {
+            delimiter: opts.delimiter,
+            quote: opts.quote,
+            escape: opts.escape,
+            comment: opts.comment,
+            trim: opts.trim,
+            flexible: opts.flexible,
+            has_headers: opts.header,
+            encoding: opts.encoding,
+        }
+    }
+}
+
+// csv 的分隔符等参数只能是单个 ascii 字符，支持 \t 表示 tab
+fn parse_csv_byte(s: &str) -> Result<u8, anyhow::Error> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ => match s.as_bytes() {
+            [b] if b.is_ascii() => Ok(*b),
+            _ => Err(anyhow::anyhow!("must be a single ascii character")),
+        },
+    }
+}
+
+fn parse_schema(schema: &str) -> Result<CsvSchema, anyhow::Error> {
+    schema.parse()
+}
+
+fn parse_validate_schema(schema: &str) -> Result<ValidateSchema, anyhow::Error> {
+    match schema {
+        "player" => Ok(ValidateSchema::Player),
+        _ => Ok(ValidateSchema::Columns(schema.parse()?)),
+    }
+}
+
+fn parse_rename(rename: &str) -> Result<(String, String), anyhow::Error> {
+    match rename.split_once('=') {
+        Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+            Ok((from.trim().to_string(), to.trim().to_string()))
+        }
+        _ => Err(anyhow::anyhow!("rename must be like \"old=new\"")),
     }
 }
 
+fn parse_row_filter(filter: &str) -> Result<RowFilter, anyhow::Error> {
+    filter.parse()
+}
+
+fn parse_data_format(format: &str) -> Result<DataFormat, anyhow::Error> {
+    format.parse()
+}
+
+fn parse_flatten_policy(policy: &str) -> Result<FlattenPolicy, anyhow::Error> {
+    policy.parse()
+}
+
+fn parse_sort_key(key: &str) -> Result<SortKey, anyhow::Error> {
+    key.parse()
+}
+
+fn parse_join_kind(how: &str) -> Result<JoinKind, anyhow::Error> {
+    how.parse()
+}
+
+fn parse_input_encoding(encoding: &str) -> Result<InputEncoding, anyhow::Error> {
+    encoding.parse()
+}
+
+fn parse_input_format(format: &str) -> Result<InputFormat, anyhow::Error> {
+    format.parse()
+}
+
 fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
@@ -51,6 +611,10 @@ impl From<OutputFormat> for &'static str {
         match format {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::MessagePack => "msgpack",
+            OutputFormat::Parquet => "parquet",
         }
     }
 }
@@ -62,6 +626,10 @@ impl FromStr for OutputFormat {
         match s {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
+            "toml" => Ok(OutputFormat::Toml),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "msgpack" | "messagepack" => Ok(OutputFormat::MessagePack),
+            "parquet" => Ok(OutputFormat::Parquet),
             _ => Err(anyhow::anyhow!("Invalid output format")),
         }
     }
@@ -72,3 +640,172 @@ impl fmt::Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(InputEncoding::Utf8),
+            "gbk" | "gb2312" | "gb18030" => Ok(InputEncoding::Gbk),
+            "latin1" | "latin-1" | "iso-8859-1" | "windows-1252" => Ok(InputEncoding::Latin1),
+            "utf-16" | "utf16" | "utf-16le" => Ok(InputEncoding::Utf16Le),
+            "utf-16be" => Ok(InputEncoding::Utf16Be),
+            "auto" => Ok(InputEncoding::Auto),
+            _ => Err(anyhow::anyhow!("Invalid input encoding")),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "xlsx" | "xls" | "ods" | "excel" => Ok(InputFormat::Excel),
+            _ => Err(anyhow::anyhow!("Invalid input format")),
+        }
+    }
+}
+
+impl FromStr for DataFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(DataFormat::Json),
+            "yaml" | "yml" => Ok(DataFormat::Yaml),
+            _ => Err(anyhow::anyhow!("Invalid input format")),
+        }
+    }
+}
+
+impl FromStr for FlattenPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(FlattenPolicy::Dot),
+            "json" => Ok(FlattenPolicy::Json),
+            _ => Err(anyhow::anyhow!("Invalid flatten policy")),
+        }
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "outer" | "full" => Ok(JoinKind::Outer),
+            _ => Err(anyhow::anyhow!("Invalid join type")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_subcommand() {
+        use crate::Opts;
+
+        let opts = Opts::try_parse_from(["rcli", "csv", "from-json", "-i", "Cargo.toml"]).unwrap();
+        let crate::SubCommand::Csv(opts) = opts.command else {
+            panic!("expected csv subcommand")
+        };
+        assert!(matches!(opts.command, Some(CsvSubCommand::FromJson(_))));
+
+        let opts = Opts::try_parse_from(["rcli", "csv", "-i", "assets/juventus.csv"]).unwrap();
+        let crate::SubCommand::Csv(opts) = opts.command else {
+            panic!("expected csv subcommand")
+        };
+        assert!(opts.command.is_none());
+        assert_eq!(opts.input.as_deref(), Some("assets/juventus.csv"));
+        assert!(Opts::try_parse_from(["rcli", "csv"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_group_by_parquet() -> anyhow::Result<()> {
+        use crate::Opts;
+
+        // 参数不合法时不应该截断已有的输出文件
+        let dir = tempfile::tempdir()?;
+        let output = dir.path().join("out.parquet");
+        std::fs::write(&output, "keep")?;
+        let opts = Opts::try_parse_from([
+            "rcli",
+            "csv",
+            "-i",
+            "assets/juventus.csv",
+            "-f",
+            "parquet",
+            "--group-by",
+            "Position",
+            "-o",
+            output.to_str().unwrap(),
+        ])?;
+        let crate::SubCommand::Csv(opts) = opts.command else {
+            panic!("expected csv subcommand")
+        };
+        assert!(opts.execute().await.is_err());
+        assert_eq!(std::fs::read_to_string(&output)?, "keep");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_excel_rejects_csv_args() -> anyhow::Result<()> {
+        use crate::Opts;
+
+        let args = ["rcli", "csv", "-i", "assets/juventus.csv", "--input-format"];
+        let opts = Opts::try_parse_from(args.iter().chain(&["xlsx", "-d", ";", "--trim"]))?;
+        let crate::SubCommand::Csv(opts) = opts.command else {
+            panic!("expected csv subcommand")
+        };
+        assert_eq!(opts.reader.csv_only_args(), ["--delimiter", "--trim"]);
+        let err = opts.execute().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--delimiter, --trim can only be used with csv input"
+        );
+
+        let opts = Opts::try_parse_from(args.iter().chain(&["xlsx", "--header", "false"]))?;
+        let crate::SubCommand::Csv(opts) = opts.command else {
+            panic!("expected csv subcommand")
+        };
+        assert!(opts.reader.csv_only_args().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rename() {
+        assert_eq!(
+            parse_rename("Kit Number=kit").unwrap(),
+            ("Kit Number".to_string(), "kit".to_string())
+        );
+        assert!(parse_rename("Kit Number").is_err());
+        assert!(parse_rename("=kit").is_err());
+    }
+
+    #[test]
+    fn test_output_format() {
+        for format in ["json", "yaml", "toml", "ndjson", "msgpack", "parquet"] {
+            assert_eq!(parse_format(format).unwrap().to_string(), format);
+        }
+        assert_eq!(parse_format("messagepack").unwrap().to_string(), "msgpack");
+        assert!(parse_format("xml").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_byte() {
+        assert_eq!(parse_csv_byte(";").unwrap(), b';');
+        assert_eq!(parse_csv_byte("\\t").unwrap(), b'\t');
+        assert_eq!(parse_csv_byte("\t").unwrap(), b'\t');
+        assert!(parse_csv_byte("ab").is_err());
+        assert!(parse_csv_byte("；").is_err());
+    }
+}
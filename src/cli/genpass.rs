@@ -1,66 +1,372 @@
-use clap::Parser;
+use std::{io, str::FromStr};
 
-use crate::{process_genpass, CmdExector};
-use zxcvbn::zxcvbn;
+use clap::{ArgAction, Parser};
+use enum_dispatch::enum_dispatch;
 
+use super::verify_file;
+use crate::{
+    estimate_strength, get_reader, passphrase_entropy, password_rng, process_genpass,
+    process_genpass_words, process_password_check, CmdExector, GenPassConfig, PasswordPolicy,
+    PasswordRng,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordFormat {
+    Plain,
+    Json,
+    Csv,
+}
+
+// 没有子命令时生成密码，子命令和生成参数不能同时使用
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
 pub struct GenPassOpts {
-    #[arg(short, long, default_value_t = 16, help = "password length")]
-    pub length: u8,
+    #[command(subcommand)]
+    pub command: Option<GenPassSubCommand>,
+
+    #[arg(
+        short,
+        long,
+        help = "password length, 16 or the policy length by default"
+    )]
+    pub length: Option<u8>,
+
+    #[arg(
+        long,
+        conflicts_with = "words",
+        help = "built-in policy (aws-iam, mysql, pin) or a yaml policy file the password must satisfy"
+    )]
+    pub policy: Option<String>,
+
+    // 为了兼容 --uppercase true 的写法，这些开关也接受 true/false 参数
+    #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set, help = "include uppercase letters")]
+    pub uppercase: bool,
+
+    #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set, help = "include lowercase letters")]
+    pub lowercase: bool,
+
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set, help = "include numbers")]
+    pub number: bool,
+
+    #[arg(long, default_value_t = false, num_args = 0..=1, default_missing_value = "true", action = ArgAction::Set, help = "include symbols")]
+    pub symbol: bool,
+
+    #[arg(long, conflicts_with_all = ["uppercase", "lowercase", "number", "symbol"], help = "custom characters to generate the password from")]
+    pub charset: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "",
+        help = "characters that never appear in the password, e.g. l1I"
+    )]
+    pub exclude: String,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "minimum number of digits, implies --number"
+    )]
+    pub min_digits: u8,
+
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "minimum number of symbols, implies --symbol"
+    )]
+    pub min_symbols: u8,
+
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), help = "number of passwords to generate")]
+    pub count: u32,
+
+    #[arg(long, default_value = "plain", value_parser = parse_password_format, help = "output format: plain, or json/csv with strength estimates")]
+    pub format: PasswordFormat,
 
-    #[arg(long, default_value = "true", help = "include uppercase letters")]
-    pub uppercase: String,
+    #[arg(
+        long,
+        help = "generate a diceware passphrase with this many words from the EFF long wordlist"
+    )]
+    pub words: Option<u8>,
 
-    #[arg(long, default_value = "true", help = "include lowercase letters")]
-    pub lowercase: String,
+    #[arg(
+        long,
+        default_value = "-",
+        requires = "words",
+        help = "separator between passphrase words"
+    )]
+    pub separator: String,
 
-    #[arg(long, default_value = "false", help = "include numbers")]
-    pub number: String,
+    #[arg(long, requires = "words", help = "capitalize each passphrase word")]
+    pub capitalize: bool,
 
-    #[arg(long, default_value = "false", help = "include symbols")]
-    pub symbol: String,
+    #[arg(
+        long,
+        requires = "words",
+        help = "append a digit to a random passphrase word"
+    )]
+    pub digit: bool,
+
+    #[arg(
+        long,
+        help = "seed a deterministic ChaCha20 generator for reproducible tests, never use it for real passwords"
+    )]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum GenPassSubCommand {
+    #[command(about = "check the strength of passwords, one per line")]
+    Check(GenPassCheckOpts),
+}
+
+#[derive(Debug, Parser)]
+pub struct GenPassCheckOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-", help = "file with one password per line, - for stdin")]
+    pub input: String,
+
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(0..=4), help = "fail when any password scores below this (0-4)")]
+    pub min_score: u8,
+
+    #[arg(long, default_value = "plain", value_parser = parse_password_format, help = "output format: plain, json or csv")]
+    pub format: PasswordFormat,
 }
 
 impl CmdExector for GenPassOpts {
-    async fn execute(self) -> anyhow::Result<()> {
-        let ret = process_genpass(
-            self.length,
-            self.uppercase,
-            self.lowercase,
-            self.number,
-            self.symbol,
-        )?;
-        println!("{}", ret);
-
-        // output password strength in stderr
-        let estimate = zxcvbn(&ret, &[]);
-        eprintln!("Password strength: {}", estimate.score());
+    async fn execute(mut self) -> anyhow::Result<()> {
+        if let Some(command) = self.command.take() {
+            return command.execute().await;
+        }
+
+        let policy = self
+            .policy
+            .as_deref()
+            .map(PasswordPolicy::load)
+            .transpose()?;
+        if let Some(seed) = self.seed {
+            eprintln!(
+                "WARNING: --seed {} makes the output reproducible by anyone who knows the seed.",
+                seed
+            );
+            eprintln!("WARNING: these passwords are NOT secret, only use them for tests!");
+        }
+        // 多个密码共用一个随机数生成器，指定 seed 时整个序列都可以复现
+        let mut rng = password_rng(self.seed);
+        let passwords = (0..self.count)
+            .map(|_| self.generate(policy.as_ref(), &mut *rng))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        match self.format {
+            PasswordFormat::Plain => {
+                for password in &passwords {
+                    println!("{}", password);
+                }
+
+                // output password strength in stderr when only one password is generated
+                if let [password] = passwords.as_slice() {
+                    let strength = estimate_strength(password);
+                    eprintln!("Password strength: {}", strength.score);
+                    eprintln!("Estimated entropy: {:.1} bits", strength.entropy_bits);
+                    if let Some(words) = self.words {
+                        eprintln!(
+                            "Passphrase entropy: {:.1} bits",
+                            passphrase_entropy(words, self.digit)
+                        );
+                    }
+                }
+            }
+            PasswordFormat::Json => {
+                let strengths = passwords
+                    .iter()
+                    .map(|p| estimate_strength(p))
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&strengths)?);
+            }
+            PasswordFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                for password in &passwords {
+                    writer.serialize(estimate_strength(password))?;
+                }
+                writer.flush()?;
+            }
+        }
 
         Ok(())
     }
 }
 
+impl CmdExector for GenPassCheckOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let mut reader = get_reader(&self.input)?;
+        let reports = process_password_check(&mut reader)?;
+
+        match self.format {
+            PasswordFormat::Plain => {
+                for report in &reports {
+                    println!(
+                        "{}\tscore={}\tguesses={}\tpatterns={}",
+                        report.password,
+                        report.score,
+                        report.guesses,
+                        report.patterns.join(",")
+                    );
+                    if let Some(warning) = &report.warning {
+                        println!("  warning: {}", warning);
+                    }
+                    for suggestion in &report.suggestions {
+                        println!("  suggestion: {}", suggestion);
+                    }
+                }
+            }
+            PasswordFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+            PasswordFormat::Csv => {
+                // csv 不支持列表字段，多个值用 ; 连接
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                writer.write_record([
+                    "password",
+                    "score",
+                    "guesses",
+                    "warning",
+                    "suggestions",
+                    "patterns",
+                ])?;
+                for report in &reports {
+                    writer.write_record([
+                        report.password.clone(),
+                        report.score.to_string(),
+                        report.guesses.to_string(),
+                        report.warning.clone().unwrap_or_default(),
+                        report.suggestions.join("; "),
+                        report.patterns.join("; "),
+                    ])?;
+                }
+                writer.flush()?;
+            }
+        }
+
+        let weak = reports.iter().filter(|r| r.score < self.min_score).count();
+        match weak {
+            0 => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "{} of {} passwords scored below {}",
+                weak,
+                reports.len(),
+                self.min_score
+            )),
+        }
+    }
+}
+
+impl GenPassOpts {
+    fn generate(
+        &self,
+        policy: Option<&PasswordPolicy>,
+        rng: &mut dyn PasswordRng,
+    ) -> anyhow::Result<String> {
+        if let Some(words) = self.words {
+            return process_genpass_words(words, &self.separator, self.capitalize, self.digit, rng);
+        }
+
+        let config = GenPassConfig::from(self);
+        let Some(policy) = policy else {
+            return process_genpass(&config, rng);
+        };
+        let config = GenPassConfig {
+            length: self.length.unwrap_or_else(|| policy.default_length()),
+            ..config
+        };
+        let password = process_genpass(&policy.apply(config)?, rng)?;
+        policy.validate(&password)?;
+        Ok(password)
+    }
+}
+
+impl From<&GenPassOpts> for GenPassConfig {
+    fn from(opts: &GenPassOpts) -> Self {
+        Self {
+            length: opts.length.unwrap_or(GenPassConfig::default().length),
+            uppercase: opts.uppercase,
+            lowercase: opts.lowercase,
+            number: opts.number,
+            symbol: opts.symbol,
+            charset: opts.charset.clone(),
+            exclude: opts.exclude.clone(),
+            min_digits: opts.min_digits,
+            min_symbols: opts.min_symbols,
+            start_with_letter: false,
+        }
+    }
+}
+
+fn parse_password_format(format: &str) -> Result<PasswordFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for PasswordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(PasswordFormat::Plain),
+            "json" => Ok(PasswordFormat::Json),
+            "csv" => Ok(PasswordFormat::Csv),
+            _ => Err(anyhow::anyhow!("Invalid password format")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_genpass() {
-        let opts = GenPassOpts {
-            length: 16,
-            uppercase: "true".to_string(),
-            lowercase: "true".to_string(),
-            number: "false".to_string(),
-            symbol: "false".to_string(),
-        };
-        let ret = process_genpass(
-            opts.length,
-            opts.uppercase,
-            opts.lowercase,
-            opts.number,
-            opts.symbol,
-        )
+        let opts = GenPassOpts::try_parse_from([
+            "genpass",
+            "--number",
+            "--symbol",
+            "false",
+            "--uppercase=false",
+            "--min-symbols",
+            "2",
+        ])
         .unwrap();
-        println!("{}", ret);
+        assert!(opts.number && !opts.symbol && !opts.uppercase && opts.lowercase);
+
+        let config = GenPassConfig::from(&opts);
+        let ret = process_genpass(&config, &mut *password_rng(None)).unwrap();
+        assert_eq!(ret.len(), 16);
+        assert!(!ret.chars().any(|c| c.is_ascii_uppercase()));
+
+        assert!(GenPassOpts::try_parse_from(["genpass", "--charset", "ab", "--number"]).is_err());
+
+        let opts = GenPassOpts::try_parse_from(["genpass", "-c", "3", "--format", "csv"]).unwrap();
+        assert_eq!((opts.count, opts.format), (3, PasswordFormat::Csv));
+        assert!(GenPassOpts::try_parse_from(["genpass", "--count", "0"]).is_err());
+        assert!(GenPassOpts::try_parse_from(["genpass", "--format", "xml"]).is_err());
+
+        // 相同的 seed 生成相同的密码序列
+        let opts =
+            GenPassOpts::try_parse_from(["genpass", "--seed", "42", "--words", "4"]).unwrap();
+        let generate = || {
+            let mut rng = password_rng(opts.seed);
+            (0..2)
+                .map(|_| opts.generate(None, &mut *rng).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let passwords = generate();
+        assert_ne!(passwords[0], passwords[1]);
+        assert_eq!(passwords, generate());
+    }
+
+    #[test]
+    fn test_genpass_check_subcommand() {
+        let opts = GenPassOpts::try_parse_from(["genpass", "check", "--min-score", "4"]).unwrap();
+        let Some(GenPassSubCommand::Check(check)) = opts.command else {
+            panic!("expected check subcommand")
+        };
+        assert_eq!((check.input.as_str(), check.min_score), ("-", 4));
+
+        assert!(GenPassOpts::try_parse_from(["genpass", "check", "--min-score", "5"]).is_err());
+        assert!(GenPassOpts::try_parse_from(["genpass", "-l", "8", "check"]).is_err());
     }
 }
@@ -0,0 +1,192 @@
+use std::{fmt, str::FromStr};
+
+use clap::Parser;
+
+use super::verify_file;
+use crate::{
+    encode_digest, format_sum_line, get_reader, process_hash, process_hash_check, CheckStatus,
+    CmdExector,
+};
+
+#[derive(Debug, Parser)]
+pub struct HashOpts {
+    // 和 sha256sum 一样，读不了的文件在执行时报告，不影响其它文件
+    #[arg(short, long, num_args = 1.., default_value = "-", conflicts_with = "check", help = "files to hash, - for stdin")]
+    pub input: Vec<String>,
+
+    #[arg(long, value_parser = parse_hash_algorithm, default_value = "sha256", help = "hash algorithm: blake3, sha256, sha512, sha1, md5 or xxh3")]
+    pub algo: HashAlgorithm,
+
+    #[arg(long, value_parser = parse_hash_format, default_value = "hex", help = "digest encoding: hex or base64")]
+    pub format: HashFormat,
+
+    #[arg(short, long, value_parser = verify_file, help = "read checksums from this file and verify them, like sha256sum --check")]
+    pub check: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Blake3,
+    Sha256,
+    Sha512,
+    Sha1,
+    Md5,
+    Xxh3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFormat {
+    Hex,
+    Base64,
+}
+
+impl HashAlgorithm {
+    /// 摘要的字节数
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Blake3 | HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha512 => 64,
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Md5 => 16,
+            HashAlgorithm::Xxh3 => 8,
+        }
+    }
+}
+
+impl CmdExector for HashOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        if let Some(sums) = &self.check {
+            let mut reader = get_reader(sums)?;
+            let report = process_hash_check(&mut reader, self.algo, self.format)?;
+            let checks = report.checks;
+            match (report.improperly_formatted, checks.is_empty()) {
+                (0, _) => {}
+                (_, true) => anyhow::bail!(
+                    "{}: no properly formatted {} checksum lines found",
+                    sums,
+                    self.algo
+                ),
+                (1, false) => eprintln!("WARNING: 1 line is improperly formatted"),
+                (n, false) => eprintln!("WARNING: {} lines are improperly formatted", n),
+            }
+            for check in &checks {
+                match check.status {
+                    CheckStatus::Ok => println!("{}: OK", check.file),
+                    CheckStatus::Failed => println!("{}: FAILED", check.file),
+                    CheckStatus::Unreadable => println!("{}: FAILED open or read", check.file),
+                }
+            }
+
+            let failed = checks
+                .iter()
+                .filter(|c| c.status != CheckStatus::Ok)
+                .count();
+            return match failed {
+                0 => Ok(()),
+                _ => Err(anyhow::anyhow!(
+                    "{} of {} computed checksums did NOT match",
+                    failed,
+                    checks.len()
+                )),
+            };
+        }
+
+        let mut unreadable = 0;
+        for input in &self.input {
+            match process_hash(input, self.algo) {
+                Ok(digest) => println!(
+                    "{}",
+                    format_sum_line(&encode_digest(&digest, self.format), input)
+                ),
+                Err(e) => {
+                    eprintln!("{}: {}", input, e);
+                    unreadable += 1;
+                }
+            }
+        }
+        match unreadable {
+            0 => Ok(()),
+            _ => Err(anyhow::anyhow!(
+                "{} of {} files could not be read",
+                unreadable,
+                self.input.len()
+            )),
+        }
+    }
+}
+
+pub fn parse_hash_algorithm(algo: &str) -> Result<HashAlgorithm, anyhow::Error> {
+    algo.parse()
+}
+
+pub fn parse_hash_format(format: &str) -> Result<HashFormat, anyhow::Error> {
+    format.parse()
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "md5" => Ok(HashAlgorithm::Md5),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            _ => Err(anyhow::anyhow!("invalid hash algorithm")),
+        }
+    }
+}
+
+impl From<HashAlgorithm> for &'static str {
+    fn from(algo: HashAlgorithm) -> Self {
+        match algo {
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl FromStr for HashFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(HashFormat::Hex),
+            "base64" => Ok(HashFormat::Base64),
+            _ => Err(anyhow::anyhow!("invalid hash format")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_opts() {
+        let opts = HashOpts::try_parse_from(["hash", "-i", "Cargo.toml", "README.md"]).unwrap();
+        assert_eq!(opts.input, ["Cargo.toml", "README.md"]);
+        assert_eq!(
+            (opts.algo, opts.format),
+            (HashAlgorithm::Sha256, HashFormat::Hex)
+        );
+
+        let opts =
+            HashOpts::try_parse_from(["hash", "--check", "Cargo.toml", "--algo", "MD5"]).unwrap();
+        assert_eq!(opts.algo, HashAlgorithm::Md5);
+        assert!(HashOpts::try_parse_from(["hash", "-i", "README.md", "-c", "Cargo.toml"]).is_err());
+        assert!(HashOpts::try_parse_from(["hash", "--algo", "crc32"]).is_err());
+    }
+}
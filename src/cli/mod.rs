@@ -1,15 +1,20 @@
 mod base64;
+mod codec;
 mod csv;
 mod genpass;
+mod hash;
 mod http;
 mod jwt;
+mod otp;
 mod text;
 
 use clap::Parser;
 use enum_dispatch::enum_dispatch;
 use std::path::{Path, PathBuf};
 
-pub use self::{base64::*, csv::*, genpass::*, http::*, jwt::*, text::*};
+pub use self::{
+    base64::*, codec::*, csv::*, genpass::*, hash::*, http::*, jwt::*, otp::*, text::*,
+};
 
 #[derive(Debug, Parser)]
 #[command(name="rcli", version, author, about, long_about = None)]
@@ -19,21 +24,46 @@ pub struct Opts {
 }
 
 // 1.此处的 csv 就是 subcommand 也就是输入的参数
+// 命令行参数只解析一次，变体大小差异无关紧要
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 #[enum_dispatch(CmdExector)]
 pub enum SubCommand {
-    #[command(name = "csv", about = "convert csv to json or yaml")]
+    #[command(
+        name = "csv",
+        about = "convert csv or excel to json, yaml, parquet and more"
+    )]
     Csv(CsvOpts),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
-    #[command(subcommand, about = "Base64 encode/decode")]
+    #[command(
+        name = "encode",
+        about = "Encode as hex, xxd, base32, base58, base64, ascii85 or z85"
+    )]
+    Encode(EncodeOpts),
+    #[command(
+        name = "decode",
+        about = "Decode hex, xxd, base32, base58, base64, ascii85 or z85"
+    )]
+    Decode(DecodeOpts),
+    #[command(
+        subcommand,
+        about = "Base64 encode/decode, same as encode/decode --codec base64"
+    )]
     Base64(Base64SubCommand),
+    #[command(
+        name = "hash",
+        about = "Hash files with blake3, sha256, sha512, sha1, md5 or xxh3"
+    )]
+    Hash(HashOpts),
     #[command(subcommand, about = "Text sign/verify")]
     Text(TextSubCommand),
     #[command(subcommand, about = "HTTP server")]
     Http(HttpSubCommand),
     #[command(subcommand, about = "jwt server")]
     Jwt(JwtSubCommand),
+    #[command(subcommand, about = "TOTP/HOTP one-time password generate/verify")]
+    Otp(OtpSubCommand),
 }
 
 fn verify_file(filename: &str) -> Result<String, &'static str> {
@@ -0,0 +1,199 @@
+use std::{fmt, str::FromStr};
+
+use clap::Parser;
+use enum_dispatch::enum_dispatch;
+
+use crate::{
+    process_hotp_generate, process_hotp_verify, process_otp_generate, process_otp_new,
+    process_otp_verify, CmdExector, OtpConfig,
+};
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExector)]
+pub enum OtpSubCommand {
+    #[command(about = "Generate the current TOTP code, or a HOTP code with --counter")]
+    Generate(OtpGenerateOpts),
+    #[command(
+        about = "Verify a TOTP code allowing some clock drift, or a HOTP code with --counter"
+    )]
+    Verify(OtpVerifyOpts),
+    #[command(about = "Create a new secret and print its otpauth:// URI")]
+    New(OtpNewOpts),
+}
+
+// generate/verify/new 共用的参数，需要和认证器 App 中的设置一致
+#[derive(Debug, Parser)]
+pub struct OtpParams {
+    #[arg(long, default_value_t = 6, value_parser = clap::value_parser!(u8).range(6..=8), help = "number of digits in the code (6-8)")]
+    pub digits: u8,
+
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..), help = "time step in seconds")]
+    pub period: u64,
+
+    #[arg(long, value_parser = parse_otp_algorithm, default_value = "sha1", help = "hmac algorithm: sha1, sha256 or sha512")]
+    pub algo: OtpAlgorithm,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpGenerateOpts {
+    #[arg(long, help = "base32 encoded secret")]
+    pub secret: String,
+
+    #[arg(
+        long,
+        help = "HOTP counter, generate a counter based code instead of TOTP"
+    )]
+    pub counter: Option<u64>,
+
+    #[command(flatten)]
+    pub params: OtpParams,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpVerifyOpts {
+    #[arg(long, help = "base32 encoded secret")]
+    pub secret: String,
+
+    #[arg(long, help = "the code to verify")]
+    pub code: String,
+
+    #[arg(
+        long,
+        help = "HOTP counter expected next, verify a counter based code instead of TOTP"
+    )]
+    pub counter: Option<u64>,
+
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(0..=10), help = "number of time steps accepted before and after now, or counters accepted after --counter (0-10)")]
+    pub window: u64,
+
+    #[command(flatten)]
+    pub params: OtpParams,
+}
+
+#[derive(Debug, Parser)]
+pub struct OtpNewOpts {
+    #[arg(
+        long,
+        help = "account name shown in the authenticator, e.g. alice@example.com"
+    )]
+    pub account: String,
+
+    #[arg(
+        long,
+        default_value = "rcli",
+        help = "issuer shown in the authenticator"
+    )]
+    pub issuer: String,
+
+    #[command(flatten)]
+    pub params: OtpParams,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    /// 新建密钥的字节数，和 HMAC 的输出长度一致
+    pub fn key_len(&self) -> usize {
+        match self {
+            OtpAlgorithm::Sha1 => 20,
+            OtpAlgorithm::Sha256 => 32,
+            OtpAlgorithm::Sha512 => 64,
+        }
+    }
+}
+
+impl CmdExector for OtpGenerateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let config = self.params.into();
+        if let Some(counter) = self.counter {
+            println!("{}", process_hotp_generate(&self.secret, counter, &config)?);
+            return Ok(());
+        }
+        let (code, remaining) = process_otp_generate(&self.secret, &config)?;
+        println!("{}", code);
+        eprintln!("valid for {}s", remaining);
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpVerifyOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let config = self.params.into();
+        if let Some(counter) = self.counter {
+            let ret = process_hotp_verify(&self.secret, &self.code, counter, self.window, &config)?;
+            return match ret {
+                // 下次验证需要从匹配的计数器之后开始
+                Some(matched) => {
+                    println!("✓ Code verified (counter {})", matched);
+                    eprintln!("next counter: {}", matched.saturating_add(1));
+                    Ok(())
+                }
+                None => anyhow::bail!("⚠ Code not verified"),
+            };
+        }
+        let ret = process_otp_verify(&self.secret, &self.code, self.window, &config)?;
+        match ret {
+            Some(0) => println!("✓ Code verified"),
+            Some(offset) => println!("✓ Code verified ({:+} time steps)", offset),
+            None => anyhow::bail!("⚠ Code not verified"),
+        }
+        Ok(())
+    }
+}
+
+impl CmdExector for OtpNewOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let (secret, uri) = process_otp_new(&self.issuer, &self.account, &self.params.into())?;
+        println!("secret: {}", secret);
+        println!("uri: {}", uri);
+        Ok(())
+    }
+}
+
+impl From<OtpParams> for OtpConfig {
+    fn from(params: OtpParams) -> Self {
+        Self {
+            digits: params.digits,
+            period: params.period,
+            algorithm: params.algo,
+        }
+    }
+}
+
+pub fn parse_otp_algorithm(algo: &str) -> Result<OtpAlgorithm, anyhow::Error> {
+    algo.parse()
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(OtpAlgorithm::Sha1),
+            "sha256" => Ok(OtpAlgorithm::Sha256),
+            "sha512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(anyhow::anyhow!("invalid otp algorithm")),
+        }
+    }
+}
+
+impl From<OtpAlgorithm> for &'static str {
+    fn from(algo: OtpAlgorithm) -> Self {
+        match algo {
+            OtpAlgorithm::Sha1 => "sha1",
+            OtpAlgorithm::Sha256 => "sha256",
+            OtpAlgorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
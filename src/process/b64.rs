@@ -1,37 +1,216 @@
 use crate::Base64Format;
 use anyhow::Result;
 use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
-    Engine as _,
+    alphabet::{self, Alphabet},
+    engine::{
+        general_purpose::{GeneralPurposeConfig, NO_PAD, PAD},
+        DecodePaddingMode, GeneralPurpose,
+    },
+    read::DecoderReader,
+    write::EncoderWriter,
 };
 
-use std::io::Read;
+use std::io::{self, Cursor, Read, Write};
 
-pub fn process_encode(reader: &mut dyn Read, format: Base64Format) -> Result<String> {
-    let mut buf = Vec::new();
-    reader.read_to_end(&mut buf)?;
+// 自动检测格式时读取的字节数
+const SNIFF_LEN: usize = 64 * 1024;
 
-    let encoded = match format {
-        Base64Format::Standard => STANDARD.encode(&buf),
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.encode(&buf),
+// MIME 规定每行最多 76 个字符，以 CRLF 换行
+const MIME_WRAP: usize = 76;
+
+/// 边读边编码，不会把整个输入读入内存；wrap 为每行的字符数，输出总是以换行结尾
+pub fn process_encode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+    wrap: Option<usize>,
+) -> Result<()> {
+    let engine = base64_engine(format)?;
+    let (wrap, newline): (_, &[u8]) = match format {
+        Base64Format::Mime => (Some(wrap.unwrap_or(MIME_WRAP)), b"\r\n"),
+        _ => (wrap, b"\n"),
     };
+    match wrap {
+        Some(width) => encode(
+            reader,
+            &mut LineWrap::new(&mut *writer, width, newline),
+            &engine,
+        )?,
+        None => encode(reader, &mut *writer, &engine)?,
+    }
+    // 最后的换行不经过 LineWrap，刚好写满一行时不会多出空行
+    writer.write_all(newline)?;
+    Ok(())
+}
+
+fn encode(reader: &mut dyn Read, writer: &mut dyn Write, engine: &GeneralPurpose) -> Result<()> {
+    let mut encoder = EncoderWriter::new(writer, engine);
+    io::copy(reader, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// 边读边解码，输入中的空白和换行会被忽略；解码结果是原始字节，可能是图片、密钥等任意二进制数据
+///
+/// 返回实际使用的格式，format 为 Auto 时根据输入的开头检测
+pub fn process_decode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Base64Format,
+) -> Result<Base64Format> {
+    if format != Base64Format::Auto {
+        decode(reader, writer, &base64_engine(format)?)?;
+        return Ok(format);
+    }
 
-    Ok(encoded)
+    let mut prefix = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut prefix)?;
+    let format = detect_base64_format(&prefix, prefix.len() < SNIFF_LEN)?;
+    if format != Base64Format::Mime && shares_crypt_alphabet(&prefix) {
+        eprintln!(
+            "warning: input is also valid bcrypt/crypt base64, decoding as {}; use --format to choose",
+            format
+        );
+    }
+    // 没有读完输入时无法确定是否有填充，所以解码时不检查填充
+    let (alphabet, config) = alphabet_config(format)?;
+    let engine = GeneralPurpose::new(
+        alphabet,
+        config.with_decode_padding_mode(DecodePaddingMode::Indifferent),
+    );
+    decode(&mut Cursor::new(prefix).chain(reader), writer, &engine)?;
+    Ok(format)
+}
+
+fn decode(reader: &mut dyn Read, writer: &mut dyn Write, engine: &GeneralPurpose) -> Result<()> {
+    let mut decoder = DecoderReader::new(SkipWhitespace(reader), engine);
+    io::copy(&mut decoder, writer)?;
+    Ok(())
+}
+
+/// 根据输入的开头推测格式，complete 表示 prefix 已经是完整的输入
+///
+/// 出现 . 时只能是 bcrypt 或 crypt，两者字符集相同只是顺序不同，无法区分，需要明确指定格式
+pub fn detect_base64_format(prefix: &[u8], complete: bool) -> Result<Base64Format> {
+    let data = prefix
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+    let has = |chars: &[u8]| data.iter().any(|b| chars.contains(b));
+    let padded = data.ends_with(b"=");
+
+    let format = if has(b"-_") {
+        match padded {
+            true => Base64Format::UrlSafePad,
+            false => Base64Format::UrlSafe,
+        }
+    } else if has(b".") {
+        anyhow::bail!(
+            "input uses the bcrypt/crypt alphabet, which can't be detected; use --format bcrypt or --format crypt"
+        );
+    } else if prefix.trim_ascii_end().contains(&b'\n') {
+        // 中间有换行说明是按行折叠的 MIME 格式
+        Base64Format::Mime
+    } else if complete && !padded && data.len() % 4 != 0 {
+        Base64Format::StandardNoPad
+    } else {
+        Base64Format::Standard
+    };
+    Ok(format)
 }
 
-pub fn process_decode(reader: &mut dyn Read, format: Base64Format) -> Result<String> {
-    let mut buf = String::new();
-    reader.read_to_string(&mut buf)?;
+/// 输入只包含字母、数字和 /、没有填充时，也可能是不含 . 的 bcrypt 或 crypt
+pub fn shares_crypt_alphabet(prefix: &[u8]) -> bool {
+    prefix
+        .iter()
+        .all(|b| b.is_ascii_alphanumeric() || *b == b'/' || b.is_ascii_whitespace())
+        && prefix.iter().any(|b| !b.is_ascii_whitespace())
+}
 
-    let buf = buf.trim();
+/// 格式对应的编码器，Auto 需要先通过 detect_base64_format 确定格式
+pub fn base64_engine(format: Base64Format) -> Result<GeneralPurpose> {
+    let (alphabet, config) = alphabet_config(format)?;
+    Ok(GeneralPurpose::new(alphabet, config))
+}
 
-    let decoded = match format {
-        Base64Format::Standard => STANDARD.decode(buf)?,
-        Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(buf)?,
+fn alphabet_config(format: Base64Format) -> Result<(&'static Alphabet, GeneralPurposeConfig)> {
+    let ret = match format {
+        Base64Format::Standard | Base64Format::Mime => (&alphabet::STANDARD, PAD),
+        Base64Format::StandardNoPad => (&alphabet::STANDARD, NO_PAD),
+        Base64Format::UrlSafe => (&alphabet::URL_SAFE, NO_PAD),
+        Base64Format::UrlSafePad => (&alphabet::URL_SAFE, PAD),
+        Base64Format::Bcrypt => (&alphabet::BCRYPT, NO_PAD),
+        Base64Format::Crypt => (&alphabet::CRYPT, NO_PAD),
+        Base64Format::Auto => anyhow::bail!("auto is only supported for decode"),
     };
+    Ok(ret)
+}
+
+// 跳过输入中的空白字符，这样带换行的 MIME 格式也能直接解码
+pub(crate) struct SkipWhitespace<R>(pub(crate) R);
+
+impl<R: Read> Read for SkipWhitespace<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let n = self.0.read(buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut len = 0;
+            for i in 0..n {
+                if !buf[i].is_ascii_whitespace() {
+                    buf[len] = buf[i];
+                    len += 1;
+                }
+            }
+            // 读到的全是空白时继续读，返回 0 会被当成结束
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
 
-    // TODO: decoded data might not be string (but for this example, we assume it is)
-    Ok(String::from_utf8(decoded)?)
+// 每写满 width 个字符插入一个换行
+pub(crate) struct LineWrap<W> {
+    inner: W,
+    width: usize,
+    newline: &'static [u8],
+    column: usize,
+}
+
+impl<W: Write> LineWrap<W> {
+    pub(crate) fn new(inner: W, width: usize, newline: &'static [u8]) -> Self {
+        Self {
+            inner,
+            width: width.max(1),
+            newline,
+            column: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for LineWrap<W> {
+    // 每次都写完整个 buf，EncoderWriter 遇到部分写入时会返回 Ok(0)，导致 io::copy 失败
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.column == self.width {
+                self.inner.write_all(self.newline)?;
+                self.column = 0;
+            }
+            let n = rest.len().min(self.width - self.column);
+            self.inner.write_all(&rest[..n])?;
+            self.column += n;
+            rest = &rest[n..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 #[cfg(test)]
@@ -39,12 +218,25 @@ mod tests {
     use super::*;
     use crate::get_reader;
 
+    fn encode(data: &[u8], format: Base64Format, wrap: Option<usize>) -> Result<String> {
+        let mut buf = Vec::new();
+        process_encode(&mut &data[..], &mut buf, format, wrap)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn decode(data: &str, format: Base64Format) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        process_decode(&mut data.as_bytes(), &mut buf, format)?;
+        Ok(buf)
+    }
+
     #[test]
     fn test_process_encode() -> Result<()> {
         let input = "fixtures/hello_world.txt";
         let format = Base64Format::Standard;
-        let encoded = process_encode(&mut get_reader(input)?, format).unwrap();
-        assert_eq!(encoded, "aGVsbG8gd29ybGQK");
+        let mut encoded = Vec::new();
+        process_encode(&mut get_reader(input)?, &mut encoded, format, None)?;
+        assert_eq!(encoded, b"aGVsbG8gd29ybGQK\n");
         Ok(())
     }
 
@@ -52,7 +244,7 @@ mod tests {
     fn test_process_encode1() -> Result<()> {
         let input = "Cargo.toml";
         let format = Base64Format::Standard;
-        assert!(process_encode(&mut get_reader(input)?, format).is_ok());
+        assert!(process_encode(&mut get_reader(input)?, &mut io::sink(), format, None).is_ok());
         Ok(())
     }
 
@@ -61,8 +253,103 @@ mod tests {
         let input = "fixtures/hello_world_encode.txt";
         let format = Base64Format::Standard;
 
-        let decoded = process_decode(&mut get_reader(input)?, format).unwrap();
-        assert_eq!(decoded, "hello world");
+        let mut decoded = Vec::new();
+        process_decode(&mut get_reader(input)?, &mut decoded, format)?;
+        assert_eq!(decoded, b"hello world");
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_decode_binary() -> Result<()> {
+        let data = [0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe];
+        let encoded = encode(&data, Base64Format::UrlSafe, None)?;
+        assert_eq!(decode(&encoded, Base64Format::UrlSafe)?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrap_and_whitespace() -> Result<()> {
+        let data = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+        let encoded = encode(&data, Base64Format::Standard, Some(76))?;
+        let lines = encoded.lines().collect::<Vec<_>>();
+        assert!(lines[..lines.len() - 1].iter().all(|l| l.len() == 76));
+        assert!(!lines[lines.len() - 1].is_empty());
+        assert!(encoded.ends_with('\n') && !encoded.ends_with("\n\n"));
+        assert_eq!(decode(&encoded, Base64Format::Standard)?, data);
+
+        // 刚好写满一行时不会多出空行
+        assert_eq!(encode(b"abc", Base64Format::Standard, Some(4))?, "YWJj\n");
+        assert_eq!(
+            decode(" aGVs\r\n bG8g\td29y bGQK \n\n", Base64Format::Standard)?,
+            b"hello world\n"
+        );
+        assert!(decode("aGVs!bG8=", Base64Format::Standard).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_formats() -> Result<()> {
+        let data = b"\xfb\xff?>hello";
+        let cases = [
+            (Base64Format::Standard, "+/8/PmhlbGxv\n"),
+            (Base64Format::StandardNoPad, "+/8/PmhlbGxv\n"),
+            (Base64Format::UrlSafe, "-_8_PmhlbGxv\n"),
+            (Base64Format::Mime, "+/8/PmhlbGxv\r\n"),
+            (Base64Format::Bcrypt, "8969NkfjZEvt\n"),
+            (Base64Format::Crypt, "yzwzDaVZP4lj\n"),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(encode(data, format, None)?, expected, "{}", format);
+            assert_eq!(decode(expected, format)?, data, "{}", format);
+        }
+
+        assert_eq!(encode(b"hi", Base64Format::UrlSafePad, None)?, "aGk=\n");
+        assert_eq!(encode(b"hi", Base64Format::StandardNoPad, None)?, "aGk\n");
+        assert!(decode("aGk", Base64Format::Standard).is_err());
+        assert!(decode("aGk=", Base64Format::UrlSafe).is_err());
+
+        let encoded = encode(&[0u8; 100], Base64Format::Mime, None)?;
+        assert_eq!(encoded.split("\r\n").next().map(str::len), Some(76));
+        assert!(encode(data, Base64Format::Auto, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_base64_format() -> Result<()> {
+        let cases = [
+            ("aGVsbG8gd29ybGQK", Base64Format::Standard),
+            ("aGk=", Base64Format::Standard),
+            ("aGk", Base64Format::StandardNoPad),
+            ("-_8_PmhlbGxv", Base64Format::UrlSafe),
+            ("-_8_aGk=", Base64Format::UrlSafePad),
+            ("AAAA\r\nAAAA\r\n", Base64Format::Mime),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(detect_base64_format(input.as_bytes(), true)?, expected);
+        }
+        // 没有读完输入时无法判断填充，解码时也不检查填充
+        assert_eq!(detect_base64_format(b"aGk", false)?, Base64Format::Standard);
+
+        // bcrypt 和 crypt 无法区分，需要明确指定格式
+        let crypt = encode(b"\0\0\0hello", Base64Format::Crypt, None)?;
+        assert!(crypt.starts_with("...."));
+        assert!(detect_base64_format(crypt.as_bytes(), true).is_err());
+        assert!(detect_base64_format(b"..8969NkfjZEvt", true).is_err());
+        let mut buf = Vec::new();
+        assert!(process_decode(&mut crypt.as_bytes(), &mut buf, Base64Format::Auto).is_err());
+
+        // 不含 . 的 bcrypt 会被当作标准格式，但会被标记为有歧义
+        assert!(shares_crypt_alphabet(b"8969NkfjZEvt"));
+        assert!(shares_crypt_alphabet(b"aGVsbG8gd29ybGQK\n"));
+        assert!(!shares_crypt_alphabet(b"aGk="));
+        assert!(!shares_crypt_alphabet(b"a+Gk"));
+
+        let mut buf = Vec::new();
+        let format = process_decode(&mut "aGk".as_bytes(), &mut buf, Base64Format::Auto)?;
+        assert_eq!(
+            (format, buf.as_slice()),
+            (Base64Format::StandardNoPad, &b"hi"[..])
+        );
         Ok(())
     }
 }
@@ -0,0 +1,427 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use anyhow::Result;
+use data_encoding::{Encoding, Specification, BASE32, BASE32_NOPAD, HEXLOWER, HEXLOWER_PERMISSIVE};
+
+use super::b64::{LineWrap, SkipWhitespace};
+use crate::{process_decode, process_encode, Base64Format, Codec};
+
+// ZeroMQ Z85 的字母表，不包含引号和反斜杠，可以直接放进源码字符串
+const Z85: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+// xxd 每行显示 16 个字节
+const XXD_LINE: usize = 16;
+
+// hex 流式处理时每次读取的字节数
+const BUF_SIZE: usize = 64 * 1024;
+
+/// hex、xxd 和 base64 边读边处理，其余编码需要完整的输入，会先读入内存；format 只用于 base64
+///
+/// wrap 为每行的字符数，xxd 有自己的行格式，不支持 wrap
+pub fn process_codec_encode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    codec: Codec,
+    format: Base64Format,
+    wrap: Option<usize>,
+) -> Result<()> {
+    match codec {
+        Codec::Base64 => return process_encode(reader, writer, format, wrap),
+        Codec::Xxd if wrap.is_some() => anyhow::bail!("xxd output can't be wrapped"),
+        Codec::Xxd => return write_xxd(reader, writer),
+        Codec::Hex => {
+            match wrap {
+                Some(width) => write_hex(reader, &mut LineWrap::new(&mut *writer, width, b"\n"))?,
+                None => write_hex(reader, &mut *writer)?,
+            }
+            writer.write_all(b"\n")?;
+            return Ok(());
+        }
+        _ => {}
+    }
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    let encoded = match codec {
+        Codec::Base32 => BASE32.encode(&buf),
+        Codec::Base32Crockford => crockford()?.encode(&buf),
+        Codec::Base58 => bs58::encode(&buf).into_string(),
+        Codec::Ascii85 => ascii85_encode(&buf),
+        Codec::Z85 => z85_encode(&buf)?,
+        Codec::Base64 | Codec::Xxd | Codec::Hex => unreachable!("streamed above"),
+    };
+
+    match wrap {
+        Some(width) if !encoded.is_empty() => {
+            for line in encoded.as_bytes().chunks(width.max(1)) {
+                writer.write_all(line)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        _ => writeln!(writer, "{}", encoded)?,
+    }
+    Ok(())
+}
+
+/// 解码时忽略空白和换行，结果原样写入 writer；base64 返回实际使用的格式，format 为 auto 时由输入推测
+pub fn process_codec_decode(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    codec: Codec,
+    format: Base64Format,
+) -> Result<Option<Base64Format>> {
+    match codec {
+        Codec::Base64 => return Ok(Some(process_decode(reader, writer, format)?)),
+        Codec::Hex => {
+            read_hex(&mut SkipWhitespace(reader), writer)?;
+            return Ok(None);
+        }
+        Codec::Xxd => {
+            read_xxd(reader, writer)?;
+            return Ok(None);
+        }
+        _ => {}
+    }
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+
+    let input = input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let decoded = match codec {
+        // 不区分大小写，填充可有可无，比如 TOTP 的密钥通常不带填充
+        Codec::Base32 => {
+            BASE32_NOPAD.decode(input.trim_end_matches('=').to_ascii_uppercase().as_bytes())?
+        }
+        Codec::Base32Crockford => crockford()?.decode(input.as_bytes())?,
+        Codec::Base58 => bs58::decode(&input).into_vec()?,
+        Codec::Ascii85 => ascii85_decode(&input)?,
+        Codec::Z85 => z85_decode(&input)?,
+        Codec::Base64 | Codec::Hex | Codec::Xxd => unreachable!("streamed above"),
+    };
+    writer.write_all(&decoded)?;
+    Ok(None)
+}
+
+fn write_hex(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        writer.write_all(HEXLOWER.encode(&buf[..n]).as_bytes())?;
+    }
+}
+
+// 每次读到的字符数可能是奇数，多出的一个留到下一次解码
+fn read_hex(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut pending = Vec::new();
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        pending.extend_from_slice(&buf[..n]);
+        decode_hex_pending(&mut pending, writer)?;
+    }
+    finish_hex_pending(&pending)
+}
+
+fn decode_hex_pending(pending: &mut Vec<u8>, writer: &mut dyn Write) -> Result<()> {
+    let even = pending.len() / 2 * 2;
+    writer.write_all(&HEXLOWER_PERMISSIVE.decode(&pending[..even])?)?;
+    pending.drain(..even);
+    Ok(())
+}
+
+fn finish_hex_pending(pending: &[u8]) -> Result<()> {
+    match pending.is_empty() {
+        true => Ok(()),
+        false => Err(anyhow::anyhow!("hex input has an odd number of digits")),
+    }
+}
+
+// Crockford base32：不区分大小写，解码时把 I/L 当作 1，O 当作 0，忽略连字符
+fn crockford() -> Result<Encoding> {
+    let mut spec = Specification::new();
+    spec.symbols.push_str("0123456789ABCDEFGHJKMNPQRSTVWXYZ");
+    spec.translate.from.push_str("abcdefghjkmnpqrstvwxyzIiLlOo");
+    spec.translate.to.push_str("ABCDEFGHJKMNPQRSTVWXYZ111100");
+    spec.ignore.push('-');
+    Ok(spec.encoding()?)
+}
+
+// 和 xxd 的默认输出一致：偏移、每两个字节一组的十六进制、可打印字符
+fn write_xxd(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = [0u8; XXD_LINE];
+    let mut offset = 0;
+    loop {
+        let n = read_full(&mut reader, &mut line)?;
+        if n == 0 {
+            return Ok(());
+        }
+        let line = &line[..n];
+        let hex = line
+            .chunks(2)
+            .map(|group| HEXLOWER.encode(group))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let text = line
+            .iter()
+            .map(|&b| match b {
+                0x20..=0x7e => b as char,
+                _ => '.',
+            })
+            .collect::<String>();
+        writeln!(writer, "{:08x}: {:<39}  {}", offset, hex, text)?;
+        offset += n;
+    }
+}
+
+// 尽量读满 buf，只有到达结尾时才会返回更少的字节
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(len)
+}
+
+// 逐行解析 xxd 的输出，忽略偏移和右侧的字符列，要求各行连续
+fn read_xxd(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<()> {
+    let mut pending = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (_, rest) = line
+            .split_once(": ")
+            .ok_or_else(|| anyhow::anyhow!("invalid xxd line: {}", line))?;
+        let groups = rest.split_once("  ").map_or(rest, |(groups, _)| groups);
+        pending.extend(groups.bytes().filter(|c| !c.is_ascii_whitespace()));
+        decode_hex_pending(&mut pending, writer)?;
+    }
+    finish_hex_pending(&pending)
+}
+
+// Adobe 风格的 Ascii85，以 <~ 开始、~> 结束，全为 0 的 4 个字节写作 z
+fn ascii85_encode(data: &[u8]) -> String {
+    let mut out = String::from("<~");
+    for chunk in data.chunks(4) {
+        let mut block = [0u8; 4];
+        block[..chunk.len()].copy_from_slice(chunk);
+        let value = u32::from_be_bytes(block);
+        if chunk.len() == 4 && value == 0 {
+            out.push('z');
+            continue;
+        }
+        // 不满 4 个字节时补 0，只输出 n + 1 个字符
+        let digits = base85_digits(value);
+        out.extend(digits[..chunk.len() + 1].iter().map(|d| (d + b'!') as char));
+    }
+    out.push_str("~>");
+    out
+}
+
+fn ascii85_decode(input: &str) -> Result<Vec<u8>> {
+    let input = input.strip_prefix("<~").unwrap_or(input);
+    let input = input.strip_suffix("~>").unwrap_or(input);
+
+    let mut out = Vec::new();
+    let mut group = Vec::with_capacity(5);
+    for c in input.bytes() {
+        match c {
+            b'z' if group.is_empty() => out.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(c - b'!');
+                if group.len() == 5 {
+                    out.extend_from_slice(&base85_value(&group)?.to_be_bytes());
+                    group.clear();
+                }
+            }
+            _ => anyhow::bail!("invalid ascii85 character {:?}", c as char),
+        }
+    }
+
+    // 最后不满 5 个字符的一组用 u 补齐，再去掉多出的字节
+    if group.len() == 1 {
+        anyhow::bail!("ascii85 input ends with a single character");
+    }
+    if !group.is_empty() {
+        let n = group.len() - 1;
+        group.resize(5, b'u' - b'!');
+        out.extend_from_slice(&base85_value(&group)?.to_be_bytes()[..n]);
+    }
+    Ok(out)
+}
+
+// Z85 规定输入长度必须是 4 的倍数，不做填充
+fn z85_encode(data: &[u8]) -> Result<String> {
+    if !data.len().is_multiple_of(4) {
+        anyhow::bail!(
+            "z85 input length must be a multiple of 4, got {}",
+            data.len()
+        );
+    }
+    Ok(data
+        .chunks_exact(4)
+        .flat_map(|chunk| base85_digits(u32::from_be_bytes(chunk.try_into().expect("4 bytes"))))
+        .map(|d| Z85[d as usize] as char)
+        .collect())
+}
+
+fn z85_decode(input: &str) -> Result<Vec<u8>> {
+    if !input.len().is_multiple_of(5) {
+        anyhow::bail!(
+            "z85 input length must be a multiple of 5, got {}",
+            input.len()
+        );
+    }
+    let mut out = Vec::with_capacity(input.len() / 5 * 4);
+    for chunk in input.as_bytes().chunks_exact(5) {
+        let digits = chunk
+            .iter()
+            .map(|c| {
+                Z85.iter()
+                    .position(|z| z == c)
+                    .map(|d| d as u8)
+                    .ok_or_else(|| anyhow::anyhow!("invalid z85 character {:?}", *c as char))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        out.extend_from_slice(&base85_value(&digits)?.to_be_bytes());
+    }
+    Ok(out)
+}
+
+// 4 个字节作为大端的 u32，转换为 5 位 85 进制数，高位在前
+fn base85_digits(mut value: u32) -> [u8; 5] {
+    let mut digits = [0; 5];
+    for digit in digits.iter_mut().rev() {
+        *digit = (value % 85) as u8;
+        value /= 85;
+    }
+    digits
+}
+
+fn base85_value(digits: &[u8]) -> Result<u32> {
+    digits
+        .iter()
+        .try_fold(0u32, |acc, &d| acc.checked_mul(85)?.checked_add(d as u32))
+        .ok_or_else(|| anyhow::anyhow!("base85 group overflows 32 bits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(data: &[u8], codec: Codec, wrap: Option<usize>) -> Result<String> {
+        let mut buf = Vec::new();
+        process_codec_encode(
+            &mut &data[..],
+            &mut buf,
+            codec,
+            Base64Format::Standard,
+            wrap,
+        )?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    fn decode(data: &str, codec: Codec) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        process_codec_decode(
+            &mut data.as_bytes(),
+            &mut buf,
+            codec,
+            Base64Format::Standard,
+        )?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_codec_vectors() -> Result<()> {
+        let cases: [(&[u8], Codec, &str); 8] = [
+            (b"foobar", Codec::Hex, "666f6f626172"),
+            (b"foobar", Codec::Base32, "MZXW6YTBOI======"),
+            (b"foobar", Codec::Base32Crockford, "CSQPYRK1E8"),
+            (b"Hello World!", Codec::Base58, "2NEpo7TZRRrLZSi2U"),
+            (b"Man sure.", Codec::Ascii85, "<~9jqo^F*2M7/c~>"),
+            (b"\0\0\0\0\x01", Codec::Ascii85, "<~z!<~>"),
+            (
+                b"\x86\x4f\xd2\x6f\xb5\x59\xf7\x5b",
+                Codec::Z85,
+                "HelloWorld",
+            ),
+            (b"foobar", Codec::Base64, "Zm9vYmFy"),
+        ];
+        for (data, codec, encoded) in cases {
+            assert_eq!(
+                encode(data, codec, None)?,
+                format!("{}\n", encoded),
+                "{}",
+                codec
+            );
+            assert_eq!(decode(encoded, codec)?, data, "{}", codec);
+        }
+
+        // 解码时比较宽松
+        assert_eq!(decode("66 6F 6f\n62", Codec::Hex)?, b"foob");
+        assert!(decode("666", Codec::Hex).is_err());
+        assert_eq!(decode("mzxw6ytboi", Codec::Base32)?, b"foobar");
+        assert_eq!(decode("csqp-yrk1-e8", Codec::Base32Crockford)?, b"foobar");
+        assert_eq!(decode("CSQPYRKLE8", Codec::Base32Crockford)?, b"foobar");
+        assert_eq!(decode("9jqo^F*2M7/c", Codec::Ascii85)?, b"Man sure.");
+
+        assert!(decode("0OIl", Codec::Base58).is_err());
+        assert!(decode("<~s8W-\"~>", Codec::Ascii85).is_err());
+        assert!(decode("Hello", Codec::Z85).is_ok());
+        assert!(decode("Hell", Codec::Z85).is_err());
+        assert!(encode(b"abc", Codec::Z85, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_xxd() -> Result<()> {
+        let data = b"hello world, this is rcli\n\x00\xff";
+        let dump = encode(data, Codec::Xxd, None)?;
+        assert_eq!(
+            dump,
+            "00000000: 6865 6c6c 6f20 776f 726c 642c 2074 6869  hello world, thi\n\
+             00000010: 7320 6973 2072 636c 690a 00ff            s is rcli...\n"
+        );
+        assert_eq!(decode(&dump, Codec::Xxd)?, data);
+        assert!(encode(data, Codec::Xxd, Some(60)).is_err());
+
+        // 超过一次读取的缓冲区大小
+        let data = (0..BUF_SIZE * 2 + 3).map(|i| i as u8).collect::<Vec<_>>();
+        let dump = encode(&data, Codec::Xxd, None)?;
+        assert_eq!(dump.lines().count(), data.len().div_ceil(XXD_LINE));
+        assert_eq!(decode(&dump, Codec::Xxd)?, data);
+        let hex = encode(&data, Codec::Hex, Some(76))?;
+        assert_eq!(decode(&hex, Codec::Hex)?, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_wrap() -> Result<()> {
+        let encoded = encode(&[0xab; 10], Codec::Hex, Some(8))?;
+        assert_eq!(encoded, "abababab\nabababab\nabab\n");
+        assert_eq!(decode(&encoded, Codec::Hex)?, [0xab; 10]);
+        assert_eq!(encode(b"", Codec::Hex, Some(8))?, "\n");
+        Ok(())
+    }
+}
@@ -1,17 +1,28 @@
-use std::fs;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
 
-use anyhow::Result;
-use csv::Reader;
+use anyhow::{Context, Result};
+use csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::cli::OutputFormat;
+use crate::{
+    cli::{InputEncoding, OutputFormat},
+    decode_reader, row_writer, write_value, CsvTransform,
+};
 
 // 1.可以使用 #[serde(rename_all = "PascalCase")] 来自动实现字段名和属性名的映射
 // 2.也可以使用 #[serde(rename = "Kit Number")] 来实现字段名和属性名的映射
+// csv validate --schema player 用它校验每一行
+#[allow(dead_code)]
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 // Name,Position,DOB,Nationality,Kit Number
-struct Player {
+pub(crate) struct Player {
     name: String,
     position: String,
     #[serde(rename = "DOB")]
@@ -21,39 +32,433 @@ struct Player {
     kit: u8,
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
+/// csv reader 的配置，对应 csv::ReaderBuilder 的各项参数
+#[derive(Debug, Clone)]
+pub struct CsvReaderConfig {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+    pub trim: bool,
+    pub flexible: bool,
+    pub has_headers: bool,
+    pub encoding: InputEncoding,
+}
+
+impl Default for CsvReaderConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            comment: None,
+            trim: false,
+            flexible: false,
+            has_headers: true,
+            encoding: InputEncoding::Utf8,
+        }
+    }
+}
+
+impl CsvReaderConfig {
+    pub fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .double_quote(self.escape.is_none())
+            .comment(self.comment)
+            .flexible(self.flexible)
+            .has_headers(self.has_headers)
+            .trim(if self.trim { Trim::All } else { Trim::None });
+        builder
+    }
+
+    /// 输出时使用和输入相同的分隔符、引号和转义字符
+    pub fn writer_builder(&self) -> WriterBuilder {
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape.unwrap_or(b'\\'))
+            .double_quote(self.escape.is_none())
+            .flexible(self.flexible);
+        builder
+    }
+
+    /// 将输入转码为 utf-8 后创建 csv reader
+    pub fn reader<'a>(&self, reader: impl Read + 'a) -> Result<csv::Reader<Box<dyn Read + 'a>>> {
+        Ok(self
+            .builder()
+            .from_reader(decode_reader(reader, self.encoding)?))
+    }
 
-    for ele in reader.records() {
-        let record = ele?;
+    /// 返回 csv 的表头，没有表头时按列数生成 col0..colN
+    pub fn headers<R: Read>(&self, reader: &mut csv::Reader<R>) -> Result<StringRecord> {
+        let headers = reader.headers()?;
+        match self.has_headers {
+            true => Ok(headers.clone()),
+            false => Ok((0..headers.len()).map(|i| format!("col{}", i)).collect()),
+        }
+    }
+}
+
+/// csv 字段可以转换成的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    Bool,
+    String,
+}
+
+/// 字段类型转换的规则：infer 为 true 时自动推断类型，columns 中指定的列优先使用指定的类型
+#[derive(Debug, Clone, Default)]
+pub struct CsvSchema {
+    pub infer: bool,
+    pub columns: HashMap<String, ColumnType>,
+}
+
+impl CsvSchema {
+    pub fn convert(&self, column: &str, field: &str) -> Result<Value> {
+        match self.columns.get(column) {
+            Some(ty) => convert_field(field, *ty),
+            None if self.infer => Ok(infer_field(field)),
+            None => Ok(Value::String(field.to_string())),
+        }
+    }
+
+    /// 转换失败时在错误信息中带上行号和列名
+    pub fn convert_in(&self, record: &StringRecord, column: &str, field: &str) -> Result<Value> {
+        self.convert(column, field).with_context(|| {
+            let line = record.position().map_or(0, |p| p.line());
+            format!("line {}, column \"{}\"", line, column)
+        })
+    }
 
-        let json_value = headers
+    /// 将一行 csv 记录和表头组合成 json 对象
+    pub fn record_to_value(&self, headers: &StringRecord, record: &StringRecord) -> Result<Value> {
+        headers
             .iter()
             .zip(record.iter())
-            .collect::<serde_json::Value>();
+            .map(|(column, field)| {
+                Ok((column.to_string(), self.convert_in(record, column, field)?))
+            })
+            .collect()
+    }
+}
 
-        ret.push(json_value);
+/// 自动推断字段类型：空字符串为 null，其次依次尝试 bool、整数、浮点数
+pub fn infer_field(field: &str) -> Value {
+    if field.is_empty() {
+        return Value::Null;
+    }
+    if let Ok(b) = parse_bool(field) {
+        return Value::Bool(b);
+    }
+    // 带前导 0 的数字（如编号、邮编）保留为字符串
+    let digits = field.strip_prefix(['-', '+']).unwrap_or(field);
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return Value::String(field.to_string());
+    }
+    if let Ok(i) = field.parse::<i64>() {
+        return Value::from(i);
     }
+    match field.parse::<f64>() {
+        Ok(f) if f.is_finite() && digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+            Value::from(f)
+        }
+        _ => Value::String(field.to_string()),
+    }
+}
 
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
+fn convert_field(field: &str, ty: ColumnType) -> Result<Value> {
+    if field.is_empty() && ty != ColumnType::String {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        ColumnType::Int => Value::from(field.parse::<i64>()?),
+        ColumnType::Float => Value::from(field.parse::<f64>()?),
+        ColumnType::Bool => Value::Bool(parse_bool(field)?),
+        ColumnType::String => Value::String(field.to_string()),
     };
+    Ok(value)
+}
+
+fn parse_bool(field: &str) -> Result<bool> {
+    match field.to_ascii_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(anyhow::anyhow!("invalid bool value: {}", field)),
+    }
+}
+
+pub fn process_csv(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: OutputFormat,
+    config: &CsvReaderConfig,
+    schema: &CsvSchema,
+    transform: &CsvTransform,
+) -> Result<()> {
+    let mut reader = config.reader(reader)?;
+    let headers = config.headers(&mut reader)?;
+    let records = reader.into_records().map(|r| r.map_err(Into::into));
+    write_records(&headers, records, writer, format, schema, transform)
+}
+
+/// 将表头和记录按照 schema 和 transform 转换后写出，csv 和 excel 等输入共用这部分逻辑
+pub fn write_records(
+    headers: &StringRecord,
+    records: impl Iterator<Item = Result<StringRecord>>,
+    writer: &mut dyn Write,
+    format: OutputFormat,
+    schema: &CsvSchema,
+    transform: &CsvTransform,
+) -> Result<()> {
+    let projection = transform.projection(headers)?;
+
+    // 分组输出需要收集所有记录，按分组值首次出现的顺序输出
+    if let Some(idx) = projection.group_index() {
+        let mut groups = serde_json::Map::new();
+        for record in records {
+            let record = record?;
+            if projection.matches(&record) {
+                let row = projection.to_value(&record, schema)?;
+                let key = record.get(idx).unwrap_or_default().to_string();
+                match groups
+                    .entry(key)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    Value::Array(rows) => rows.push(row),
+                    _ => unreachable!("group values are always arrays"),
+                }
+            }
+        }
+        return write_value(format, writer, &Value::Object(groups));
+    }
+
+    // 逐条读取、转换并写出，内存占用不随文件大小增长
+    let mut writer = row_writer(format, writer);
+    for record in records {
+        let record = record?;
+        if projection.matches(&record) {
+            let row = projection.to_value(&record, schema)?;
+            writer.write_row(&row)?;
+        }
+    }
 
-    fs::write(output, content)?;
-    Ok(())
+    writer.finish()
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "int" => Ok(ColumnType::Int),
+            "float" => Ok(ColumnType::Float),
+            "bool" => Ok(ColumnType::Bool),
+            "string" => Ok(ColumnType::String),
+            _ => Err(anyhow::anyhow!("invalid column type: {}", s)),
+        }
+    }
+}
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+            ColumnType::String => "string",
+        }
+    }
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+/// 解析形如 "Kit Number=int,DOB=string" 的列类型声明
+impl FromStr for CsvSchema {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let columns = s
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| match item.rsplit_once('=') {
+                Some((column, ty)) => Ok((column.trim().to_string(), ty.trim().parse()?)),
+                None => Err(anyhow::anyhow!("invalid schema item: {}", item)),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            infer: false,
+            columns,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{get_reader, get_writer};
 
     #[test]
     fn test_process_csv() {
         let input = "assets/juventus.csv";
         let output = "assets/juventus.json";
-        process_csv(input, output.to_string(), OutputFormat::Json).unwrap();
+        process_csv(
+            &mut get_reader(input).unwrap(),
+            &mut get_writer(output).unwrap(),
+            OutputFormat::Json,
+            &CsvReaderConfig::default(),
+            &CsvSchema::default(),
+            &CsvTransform::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_process_csv_reader_writer() -> Result<()> {
+        let mut reader = "Name,Kit Number\nBuffon,77\n".as_bytes();
+        let mut buf = Vec::new();
+        let schema = CsvSchema {
+            infer: true,
+            ..Default::default()
+        };
+        process_csv(
+            &mut reader,
+            &mut buf,
+            OutputFormat::Ndjson,
+            &CsvReaderConfig::default(),
+            &schema,
+            &CsvTransform::default(),
+        )?;
+        assert_eq!(buf, b"{\"Name\":\"Buffon\",\"Kit Number\":77}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_reader_config() -> Result<()> {
+        let config = CsvReaderConfig {
+            delimiter: b';',
+            comment: Some(b'#'),
+            trim: true,
+            ..Default::default()
+        };
+        let data = "# comment\nName ; Kit\n Buffon ; 77\n";
+        let mut reader = config.builder().from_reader(data.as_bytes());
+        let headers = config.headers(&mut reader)?;
+        assert_eq!(headers, vec!["Name", "Kit"]);
+
+        let record = reader.records().next().unwrap()?;
+        assert_eq!(record, vec!["Buffon", "77"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_reader_config_no_header() -> Result<()> {
+        let config = CsvReaderConfig {
+            delimiter: b'\t',
+            has_headers: false,
+            ..Default::default()
+        };
+        let data = "Buffon\t77\nChiellini\t3\n";
+        let mut reader = config.builder().from_reader(data.as_bytes());
+        let headers = config.headers(&mut reader)?;
+        assert_eq!(headers, vec!["col0", "col1"]);
+        assert_eq!(reader.records().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_field() {
+        assert_eq!(infer_field(""), Value::Null);
+        assert_eq!(infer_field("77"), Value::from(77));
+        assert_eq!(infer_field("-3"), Value::from(-3));
+        assert_eq!(infer_field("1.5"), Value::from(1.5));
+        assert_eq!(infer_field("TRUE"), Value::Bool(true));
+        assert_eq!(infer_field("007"), Value::from("007"));
+        assert_eq!(infer_field("NaN"), Value::from("NaN"));
+        assert_eq!(infer_field("Italy"), Value::from("Italy"));
+    }
+
+    #[test]
+    fn test_csv_schema() -> Result<()> {
+        let mut schema: CsvSchema = "Kit Number=int, DOB=string".parse()?;
+        schema.infer = true;
+        assert_eq!(schema.columns["Kit Number"], ColumnType::Int);
+
+        let headers = StringRecord::from(vec!["Name", "DOB", "Kit Number", "Score"]);
+        let record = StringRecord::from(vec!["Buffon", "1978", "77", "9.5"]);
+        let value = schema.record_to_value(&headers, &record)?;
+        assert_eq!(
+            value,
+            serde_json::json!({"Name": "Buffon", "DOB": "1978", "Kit Number": 77, "Score": 9.5})
+        );
+
+        let record = StringRecord::from(vec!["Buffon", "1978", "x", "9.5"]);
+        assert!(schema.record_to_value(&headers, &record).is_err());
+        assert!("Kit Number".parse::<CsvSchema>().is_err());
+        assert!("Kit Number=date".parse::<CsvSchema>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_csv_group_by() -> Result<()> {
+        let mut reader = get_reader("assets/juventus.csv")?;
+        let mut buf = Vec::new();
+        let transform = CsvTransform {
+            select: vec!["Name".to_string()],
+            group_by: Some("Position".to_string()),
+            ..Default::default()
+        };
+        process_csv(
+            &mut reader,
+            &mut buf,
+            OutputFormat::Json,
+            &CsvReaderConfig::default(),
+            &CsvSchema::default(),
+            &transform,
+        )?;
+
+        let groups: serde_json::Map<String, Value> = serde_json::from_slice(&buf)?;
+        assert_eq!(groups.keys().next().map(String::as_str), Some("Goalkeeper"));
+        assert_eq!(groups["Goalkeeper"].as_array().unwrap().len(), 4);
+        assert_eq!(
+            groups["Goalkeeper"][0],
+            serde_json::json!({"Name": "Wojciech Szczesny"})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_toml() -> Result<()> {
+        let mut reader = "Name,Kit Number,Nickname\nBuffon,77,\nChiellini,3,King\n".as_bytes();
+        let mut buf = Vec::new();
+        let schema = CsvSchema {
+            infer: true,
+            ..Default::default()
+        };
+        process_csv(
+            &mut reader,
+            &mut buf,
+            OutputFormat::Toml,
+            &CsvReaderConfig::default(),
+            &schema,
+            &CsvTransform::default(),
+        )?;
+        let table: toml::Table = String::from_utf8(buf)?.parse()?;
+        let rows = table["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["Kit Number"].as_integer(), Some(77));
+        assert!(rows[0].get("Nickname").is_none());
+        assert_eq!(rows[1]["Nickname"].as_str(), Some("King"));
+        Ok(())
     }
 }
@@ -0,0 +1,506 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fs::File,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use tempfile::TempPath;
+
+use crate::{
+    cli::{JoinKind, OutputFormat},
+    column_index, row_writer, CsvReaderConfig, CsvSchema, RowWriter,
+};
+
+// 每次归并同时打开的临时文件数，超过时分多轮归并，避免超出文件描述符的限制
+const MERGE_FAN_IN: usize = 64;
+
+/// 排序的列，形如 "Kit Number" 或 "Kit Number:desc"
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub desc: bool,
+}
+
+/// 排序、去重、合并的输出：默认写回 csv，指定 format 时按 OutputFormat 输出
+enum CsvSink<'a> {
+    Csv(Box<csv::Writer<&'a mut dyn Write>>),
+    Rows {
+        headers: StringRecord,
+        writer: Box<dyn RowWriter + 'a>,
+    },
+}
+
+impl<'a> CsvSink<'a> {
+    fn new(
+        writer: &'a mut dyn Write,
+        format: Option<OutputFormat>,
+        config: &CsvReaderConfig,
+        headers: &StringRecord,
+    ) -> Result<Self> {
+        match format {
+            Some(format) => Ok(CsvSink::Rows {
+                headers: headers.clone(),
+                writer: row_writer(format, writer),
+            }),
+            None => {
+                let mut writer = config.writer_builder().from_writer(writer);
+                if config.has_headers {
+                    writer.write_record(headers)?;
+                }
+                Ok(CsvSink::Csv(Box::new(writer)))
+            }
+        }
+    }
+
+    fn write(&mut self, record: &StringRecord) -> Result<()> {
+        match self {
+            CsvSink::Csv(writer) => writer.write_record(record)?,
+            CsvSink::Rows { headers, writer } => {
+                writer.write_row(&CsvSchema::default().record_to_value(headers, record)?)?
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            CsvSink::Csv(mut writer) => writer.flush()?,
+            CsvSink::Rows { mut writer, .. } => writer.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// 外部排序：每读取 chunk_rows 行排序后写入临时文件，最后多路归并，内存只和 chunk_rows 有关
+pub fn process_csv_sort(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Option<OutputFormat>,
+    config: &CsvReaderConfig,
+    keys: &[SortKey],
+    chunk_rows: usize,
+) -> Result<()> {
+    if chunk_rows == 0 {
+        anyhow::bail!("chunk size must be greater than 0");
+    }
+    let mut reader = config.reader(reader)?;
+    let headers = config.headers(&mut reader)?;
+    let keys = keys
+        .iter()
+        .map(|key| Ok((column_index(&headers, &key.column)?, key.desc)))
+        .collect::<Result<Vec<_>>>()?;
+    let cmp = |a: &StringRecord, b: &StringRecord| compare_records(a, b, &keys);
+
+    let mut chunks = Vec::new();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        rows.push(record?);
+        if rows.len() >= chunk_rows {
+            rows.sort_by(cmp);
+            chunks.push(spill(&rows)?);
+            rows.clear();
+        }
+    }
+    rows.sort_by(cmp);
+
+    let mut sink = CsvSink::new(writer, format, config, &headers)?;
+    // 数据能放进一个 chunk 时不需要临时文件
+    if chunks.is_empty() {
+        for row in &rows {
+            sink.write(row)?;
+        }
+        return sink.finish();
+    }
+    if !rows.is_empty() {
+        chunks.push(spill(&rows)?);
+    }
+
+    // 每轮把相邻的 MERGE_FAN_IN 个文件归并成一个，顺序不变所以排序仍然稳定
+    while chunks.len() > MERGE_FAN_IN {
+        let mut merged = Vec::new();
+        let mut iter = chunks.into_iter().peekable();
+        while iter.peek().is_some() {
+            let group = iter.by_ref().take(MERGE_FAN_IN).collect::<Vec<_>>();
+            let path = tempfile::NamedTempFile::new()?.into_temp_path();
+            let mut writer = csv::WriterBuilder::new().flexible(true).from_path(&path)?;
+            merge_chunks(&group, &keys, |record| Ok(writer.write_record(record)?))?;
+            writer.flush()?;
+            merged.push(path);
+        }
+        chunks = merged;
+    }
+    merge_chunks(&chunks, &keys, |record| sink.write(record))?;
+    sink.finish()
+}
+
+/// 按 keys 中的列去重，保留第一次出现的行；keys 为空时按整行去重
+pub fn process_csv_dedup(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Option<OutputFormat>,
+    config: &CsvReaderConfig,
+    keys: &[String],
+) -> Result<()> {
+    let mut reader = config.reader(reader)?;
+    let headers = config.headers(&mut reader)?;
+    let keys = keys
+        .iter()
+        .map(|key| column_index(&headers, key))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut sink = CsvSink::new(writer, format, config, &headers)?;
+    let mut seen = HashSet::new();
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let key = match keys.is_empty() {
+            true => record.iter().map(String::from).collect::<Vec<_>>(),
+            false => keys
+                .iter()
+                .map(|&i| record.get(i).unwrap_or_default().to_string())
+                .collect(),
+        };
+        if seen.insert(key) {
+            sink.write(&record)?;
+        }
+    }
+    sink.finish()
+}
+
+/// 按 on 列合并两个 csv，右表会被完整读入内存，左表逐行读取
+pub fn process_csv_merge(
+    left: &mut dyn Read,
+    right: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: Option<OutputFormat>,
+    config: &CsvReaderConfig,
+    on: &str,
+    how: JoinKind,
+) -> Result<()> {
+    let mut right = config.reader(right)?;
+    let right_headers = config.headers(&mut right)?;
+    let right_on = column_index(&right_headers, on)?;
+    let right_rows = right.records().collect::<Result<Vec<_>, _>>()?;
+    let mut index: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, row) in right_rows.iter().enumerate() {
+        index
+            .entry(row.get(right_on).unwrap_or_default())
+            .or_default()
+            .push(i);
+    }
+
+    let mut left = config.reader(left)?;
+    let left_headers = config.headers(&mut left)?;
+    let left_on = column_index(&left_headers, on)?;
+
+    // 右表中除 on 以外的列接在左表之后，和左表重名的列加上 _right 后缀
+    let right_columns = (0..right_headers.len())
+        .filter(|&i| i != right_on)
+        .collect::<Vec<_>>();
+    let mut headers = left_headers.clone();
+    for &i in &right_columns {
+        let name = &right_headers[i];
+        match left_headers.iter().any(|h| h == name) {
+            true => headers.push_field(&format!("{}_right", name)),
+            false => headers.push_field(name),
+        }
+    }
+
+    let join = |left: &StringRecord, right: Option<&StringRecord>| {
+        let mut record = left.clone();
+        for &i in &right_columns {
+            record.push_field(right.and_then(|r| r.get(i)).unwrap_or_default());
+        }
+        record
+    };
+
+    let mut sink = CsvSink::new(writer, format, config, &headers)?;
+    let mut matched = vec![false; right_rows.len()];
+    let mut record = StringRecord::new();
+    while left.read_record(&mut record)? {
+        match index.get(record.get(left_on).unwrap_or_default()) {
+            Some(rows) => {
+                for &i in rows {
+                    matched[i] = true;
+                    sink.write(&join(&record, Some(&right_rows[i])))?;
+                }
+            }
+            None if how != JoinKind::Inner => sink.write(&join(&record, None))?,
+            None => {}
+        }
+    }
+
+    // outer join 时输出右表中没有匹配的行，左表的列除 on 以外为空
+    if how == JoinKind::Outer {
+        for (row, _) in right_rows.iter().zip(matched).filter(|(_, m)| !m) {
+            let left = (0..left_headers.len())
+                .map(|i| match i == left_on {
+                    true => row.get(right_on).unwrap_or_default(),
+                    false => "",
+                })
+                .collect::<StringRecord>();
+            sink.write(&join(&left, Some(row)))?;
+        }
+    }
+    sink.finish()
+}
+
+// 排序后的 chunk 写入临时文件，只保留路径，归并时再打开；TempPath 销毁时删除文件
+// 和 merge_chunks 的 reader 一样允许列数不同，否则 --flexible 的输入在溢写时会失败
+fn spill(rows: &[StringRecord]) -> Result<TempPath> {
+    let path = tempfile::NamedTempFile::new()?.into_temp_path();
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_path(&path)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+// 多路归并已排序的临时文件
+fn merge_chunks(
+    chunks: &[TempPath],
+    keys: &[(usize, bool)],
+    mut emit: impl FnMut(&StringRecord) -> Result<()>,
+) -> Result<()> {
+    let mut readers = chunks
+        .iter()
+        .map(|path| {
+            Ok(csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(File::open(path)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (chunk, reader) in readers.iter_mut().enumerate() {
+        let mut record = StringRecord::new();
+        if reader.read_record(&mut record)? {
+            heap.push(HeapEntry {
+                record,
+                chunk,
+                keys,
+            });
+        }
+    }
+    while let Some(mut entry) = heap.pop() {
+        emit(&entry.record)?;
+        if readers[entry.chunk].read_record(&mut entry.record)? {
+            heap.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn compare_records(a: &StringRecord, b: &StringRecord, keys: &[(usize, bool)]) -> Ordering {
+    keys.iter()
+        .map(|&(i, desc)| {
+            let ord = sort_compare(a.get(i).unwrap_or_default(), b.get(i).unwrap_or_default());
+            if desc {
+                ord.reverse()
+            } else {
+                ord
+            }
+        })
+        .find(|ord| ord.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// 排序要求全序，所以和 compare 不同：数字排在字符串之前，数字之间按数值比较
+fn sort_compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// 多路归并时的堆元素，BinaryHeap 是大顶堆，所以比较结果取反；相等时先输出靠前的 chunk，保证排序稳定
+struct HeapEntry<'a> {
+    record: StringRecord,
+    chunk: usize,
+    keys: &'a [(usize, bool)],
+}
+
+impl Ord for HeapEntry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_records(&other.record, &self.record, self.keys)
+            .then_with(|| other.chunk.cmp(&self.chunk))
+    }
+}
+
+impl PartialOrd for HeapEntry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry<'_> {}
+
+impl FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, desc) = match s.rsplit_once(':') {
+            Some((column, "desc")) => (column, true),
+            Some((column, "asc")) => (column, false),
+            _ => (s, false),
+        };
+        let column = column.trim();
+        if column.is_empty() {
+            anyhow::bail!("invalid sort key: {}", s);
+        }
+        Ok(Self {
+            column: column.to_string(),
+            desc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(f: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<String> {
+        let mut buf = Vec::new();
+        f(&mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_parse_sort_key() -> Result<()> {
+        let key: SortKey = "Kit Number:desc".parse()?;
+        assert_eq!((key.column.as_str(), key.desc), ("Kit Number", true));
+        let key: SortKey = "a:b".parse()?;
+        assert_eq!((key.column.as_str(), key.desc), ("a:b", false));
+        assert!(":desc".parse::<SortKey>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_sort() -> Result<()> {
+        let data = "name,kit\nd,10\na,9\nc,x\nb,9\ne,100\n";
+        let config = CsvReaderConfig::default();
+        let keys = ["kit".parse()?, "name:desc".parse()?];
+        // chunk_rows 为 2 时会写出 3 个临时文件
+        for chunk_rows in [2, 100] {
+            let output = run(|w| {
+                process_csv_sort(&mut data.as_bytes(), w, None, &config, &keys, chunk_rows)
+            })?;
+            assert_eq!(output, "name,kit\nb,9\na,9\nd,10\ne,100\nc,x\n");
+        }
+
+        // 超过 MERGE_FAN_IN 个临时文件时分多轮归并，相同的值保持输入顺序
+        let rows = MERGE_FAN_IN * 3 + 7;
+        let data = (0..rows).fold("id,kit\n".to_string(), |acc, i| {
+            acc + &format!("{},{}\n", i, (rows - i) / 2)
+        });
+        let keys = ["kit".parse()?];
+        let output = run(|w| process_csv_sort(&mut data.as_bytes(), w, None, &config, &keys, 1))?;
+        let mut expected = (0..rows).collect::<Vec<_>>();
+        expected.sort_by_key(|i| (rows - i) / 2);
+        let expected = expected.iter().fold("id,kit\n".to_string(), |acc, i| {
+            acc + &format!("{},{}\n", i, (rows - i) / 2)
+        });
+        assert_eq!(output, expected);
+
+        // --flexible 的输入列数不一，溢写和多轮归并时也要保留原样
+        let data = (0..MERGE_FAN_IN + 6).fold("a,b\n".to_string(), |acc, i| {
+            acc + &match i % 3 {
+                0 => format!("{}\n", 1000 - i),
+                1 => format!("{},x\n", 1000 - i),
+                _ => format!("{},y,z\n", 1000 - i),
+            }
+        });
+        let config = CsvReaderConfig {
+            flexible: true,
+            ..Default::default()
+        };
+        let keys = ["a".parse()?];
+        let sorted = run(|w| process_csv_sort(&mut data.as_bytes(), w, None, &config, &keys, 1))?;
+        let in_memory =
+            run(|w| process_csv_sort(&mut data.as_bytes(), w, None, &config, &keys, 1000))?;
+        assert_eq!(sorted, in_memory);
+        assert!(sorted.starts_with("a,b\n931\n932,y,z\n933,x\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sink_quote_escape() -> Result<()> {
+        let data = "name;note\n'Buffon';'it\\'s; fine'\n";
+        let config = CsvReaderConfig {
+            delimiter: b';',
+            quote: b'\'',
+            escape: Some(b'\\'),
+            ..Default::default()
+        };
+        let output = run(|w| process_csv_dedup(&mut data.as_bytes(), w, None, &config, &[]))?;
+        assert_eq!(output, "name;note\nBuffon;'it\\'s; fine'\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup() -> Result<()> {
+        let data = "name,team\nBuffon,Juventus\nBuffon,Parma\nBuffon,Juventus\n";
+        let config = CsvReaderConfig::default();
+        let output = run(|w| process_csv_dedup(&mut data.as_bytes(), w, None, &config, &[]))?;
+        assert_eq!(output, "name,team\nBuffon,Juventus\nBuffon,Parma\n");
+
+        let keys = ["name".to_string()];
+        let output = run(|w| {
+            process_csv_dedup(
+                &mut data.as_bytes(),
+                w,
+                Some(OutputFormat::Ndjson),
+                &config,
+                &keys,
+            )
+        })?;
+        assert_eq!(output, "{\"name\":\"Buffon\",\"team\":\"Juventus\"}\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge() -> Result<()> {
+        let left = "id,name\n1,Buffon\n2,Chiellini\n";
+        let right = "id,name,kit\n1,Gigi,77\n3,Rugani,24\n";
+        let config = CsvReaderConfig::default();
+        let merge = |how| {
+            run(|w| {
+                process_csv_merge(
+                    &mut left.as_bytes(),
+                    &mut right.as_bytes(),
+                    w,
+                    None,
+                    &config,
+                    "id",
+                    how,
+                )
+            })
+        };
+
+        assert_eq!(
+            merge(JoinKind::Inner)?,
+            "id,name,name_right,kit\n1,Buffon,Gigi,77\n"
+        );
+        assert_eq!(
+            merge(JoinKind::Left)?,
+            "id,name,name_right,kit\n1,Buffon,Gigi,77\n2,Chiellini,,\n"
+        );
+        assert_eq!(
+            merge(JoinKind::Outer)?,
+            "id,name,name_right,kit\n1,Buffon,Gigi,77\n2,Chiellini,,\n3,,Rugani,24\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::cli::{DataFormat, FlattenPolicy};
+
+/// 将 json/yaml 中的对象数组转换成 csv，表头为所有对象 key 的并集（按首次出现的顺序）
+pub fn process_to_csv(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: DataFormat,
+    flatten: FlattenPolicy,
+    delimiter: u8,
+) -> Result<()> {
+    let value: Value = match format {
+        DataFormat::Json => serde_json::from_reader(reader)?,
+        DataFormat::Yaml => serde_yaml::from_reader(reader)?,
+    };
+
+    let rows = match value {
+        Value::Array(arr) => arr,
+        obj @ Value::Object(_) => vec![obj],
+        _ => anyhow::bail!("input must be an array of objects"),
+    };
+
+    let rows = rows
+        .into_iter()
+        .map(|row| match row {
+            Value::Object(obj) => Ok(flatten_object(obj, flatten)),
+            _ => Err(anyhow::anyhow!("input must be an array of objects")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // 需要先得到所有的 key 才能写表头，所以这里会保留所有记录
+    let mut headers: Vec<String> = Vec::new();
+    for row in &rows {
+        for key in row.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    writer.write_record(&headers)?;
+    for row in &rows {
+        writer.write_record(
+            headers
+                .iter()
+                .map(|h| row.get(h).and_then(Value::as_str).unwrap_or_default()),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 按照 flatten 策略把对象展开成 列名 -> 单元格 的映射
+pub fn flatten_object(obj: Map<String, Value>, policy: FlattenPolicy) -> Map<String, Value> {
+    let mut ret = Map::new();
+    for (key, value) in obj {
+        flatten_value(key, value, policy, &mut ret);
+    }
+    ret.into_iter()
+        .map(|(k, v)| (k, Value::String(to_cell(v))))
+        .collect()
+}
+
+fn flatten_value(key: String, value: Value, policy: FlattenPolicy, ret: &mut Map<String, Value>) {
+    match (policy, value) {
+        (FlattenPolicy::Dot, Value::Object(obj)) if !obj.is_empty() => {
+            for (k, v) in obj {
+                flatten_value(format!("{}.{}", key, k), v, policy, ret);
+            }
+        }
+        (FlattenPolicy::Dot, Value::Array(arr)) if !arr.is_empty() => {
+            for (i, v) in arr.into_iter().enumerate() {
+                flatten_value(format!("{}.{}", key, i), v, policy, ret);
+            }
+        }
+        (_, value) => {
+            ret.insert(key, value);
+        }
+    }
+}
+
+/// 标量直接输出，null 为空，嵌套的对象/数组输出为 json 字符串
+pub fn to_cell(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten_object() {
+        let obj = json!({"name": "Buffon", "kit": 77, "address": {"city": "Turin"}, "tags": ["gk"], "note": null});
+        let Value::Object(obj) = obj else {
+            unreachable!()
+        };
+
+        let ret = flatten_object(obj.clone(), FlattenPolicy::Dot);
+        assert_eq!(
+            Value::Object(ret),
+            json!({"name": "Buffon", "kit": "77", "address.city": "Turin", "tags.0": "gk", "note": ""})
+        );
+
+        let ret = flatten_object(obj, FlattenPolicy::Json);
+        assert_eq!(ret["address"], json!(r#"{"city":"Turin"}"#));
+        assert_eq!(ret["tags"], json!(r#"["gk"]"#));
+    }
+}
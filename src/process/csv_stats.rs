@@ -0,0 +1,345 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{infer_field, row_writer, CsvReaderConfig, OutputFormat};
+
+// 每列精确计数的不同值上限，超过后 distinct 使用 HyperLogLog 估算
+const MAX_EXACT_DISTINCT: usize = 100_000;
+
+// HyperLogLog 的精度，2^12 个寄存器，标准误差约 1.6%
+const HLL_PRECISION: u32 = 12;
+
+#[derive(Debug, Serialize)]
+pub struct ColumnStats {
+    pub column: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub count: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    pub distinct_approx: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mean: Option<f64>,
+    pub top: Vec<TopValue>,
+    // 不同值超过上限后新出现的值不再计数，top 可能漏掉后面出现的高频值
+    pub top_approx: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TopValue {
+    pub value: String,
+    pub count: usize,
+}
+
+/// 扫描过程中每列的累计状态
+struct ColumnAccumulator {
+    count: usize,
+    nulls: usize,
+    ints: usize,
+    floats: usize,
+    bools: usize,
+    min: f64,
+    max: f64,
+    sum: f64,
+    counts: HashMap<String, usize>,
+    capped: bool,
+    hll: HyperLogLog,
+}
+
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << HLL_PRECISION],
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let idx = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[idx] = self.registers[idx].max(rank as u8);
+    }
+
+    fn count(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+
+        // 基数较小时使用线性计数修正
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        match estimate <= 2.5 * m && zeros > 0 {
+            true => (m * (m / zeros as f64).ln()).round() as usize,
+            false => estimate.round() as usize,
+        }
+    }
+}
+
+impl ColumnAccumulator {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            nulls: 0,
+            ints: 0,
+            floats: 0,
+            bools: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            sum: 0.0,
+            counts: HashMap::new(),
+            capped: false,
+            hll: HyperLogLog::new(),
+        }
+    }
+
+    fn add(&mut self, field: &str) {
+        self.count += 1;
+        let number = match infer_field(field) {
+            Value::Null => {
+                self.nulls += 1;
+                return;
+            }
+            Value::Bool(_) => {
+                self.bools += 1;
+                None
+            }
+            Value::Number(n) if n.is_i64() => {
+                self.ints += 1;
+                n.as_f64()
+            }
+            Value::Number(n) => {
+                self.floats += 1;
+                n.as_f64()
+            }
+            _ => None,
+        };
+        if let Some(n) = number {
+            self.min = self.min.min(n);
+            self.max = self.max.max(n);
+            self.sum += n;
+        }
+
+        self.hll.insert(field);
+        let distinct = self.counts.len();
+        match self.counts.get_mut(field) {
+            Some(count) => *count += 1,
+            None if distinct < MAX_EXACT_DISTINCT => {
+                self.counts.insert(field.to_string(), 1);
+            }
+            None => self.capped = true,
+        }
+    }
+
+    fn finish(self, column: String, top: usize) -> ColumnStats {
+        let values = self.count - self.nulls;
+        let ty = match values {
+            0 => "null",
+            n if n == self.ints => "int",
+            n if n == self.ints + self.floats => "float",
+            n if n == self.bools => "bool",
+            _ => "string",
+        };
+        let numeric = values > 0 && (ty == "int" || ty == "float");
+
+        let mut top_values = self
+            .counts
+            .into_iter()
+            .map(|(value, count)| TopValue { value, count })
+            .collect::<Vec<_>>();
+        top_values.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        let distinct = match self.capped {
+            true => self.hll.count(),
+            false => top_values.len(),
+        };
+        top_values.truncate(top);
+
+        ColumnStats {
+            column,
+            ty: ty.to_string(),
+            count: self.count,
+            nulls: self.nulls,
+            distinct,
+            distinct_approx: self.capped,
+            min: numeric.then_some(self.min),
+            max: numeric.then_some(self.max),
+            mean: numeric.then(|| self.sum / values as f64),
+            top: top_values,
+            top_approx: self.capped,
+        }
+    }
+}
+
+pub fn process_csv_stats(
+    reader: &mut dyn Read,
+    config: &CsvReaderConfig,
+    top: usize,
+) -> Result<Vec<ColumnStats>> {
+    let mut reader = config.reader(reader)?;
+    let headers = config.headers(&mut reader)?;
+    let mut columns = headers
+        .iter()
+        .map(|_| ColumnAccumulator::new())
+        .collect::<Vec<_>>();
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        for (acc, field) in columns.iter_mut().zip(record.iter()) {
+            acc.add(field);
+        }
+    }
+
+    Ok(headers
+        .iter()
+        .zip(columns)
+        .map(|(column, acc)| acc.finish(column.to_string(), top))
+        .collect())
+}
+
+/// 输出为 json/yaml 等格式时，每列的统计是一条记录
+pub fn write_csv_stats(
+    writer: &mut dyn Write,
+    stats: &[ColumnStats],
+    format: OutputFormat,
+) -> Result<()> {
+    let mut writer = row_writer(format, writer);
+    for stat in stats {
+        writer.write_row(&serde_json::to_value(stat)?)?;
+    }
+    writer.finish()
+}
+
+/// 以对齐的文本表格输出
+pub fn write_csv_stats_table(writer: &mut dyn Write, stats: &[ColumnStats]) -> Result<()> {
+    let number = |n: Option<f64>| n.map_or_else(String::new, |n| format!("{}", n));
+    let mut rows = vec![[
+        "column", "type", "count", "nulls", "distinct", "min", "max", "mean", "top",
+    ]
+    .map(String::from)];
+    for stat in stats {
+        let distinct = match stat.distinct_approx {
+            true => format!("~{}", stat.distinct),
+            false => stat.distinct.to_string(),
+        };
+        let approx = if stat.top_approx { "~" } else { "" };
+        let top = stat
+            .top
+            .iter()
+            .map(|t| format!("{} ({}{})", t.value, approx, t.count))
+            .collect::<Vec<_>>()
+            .join(", ");
+        rows.push([
+            stat.column.clone(),
+            stat.ty.clone(),
+            stat.count.to_string(),
+            stat.nulls.to_string(),
+            distinct,
+            number(stat.min),
+            number(stat.max),
+            number(stat.mean.map(|m| (m * 100.0).round() / 100.0)),
+            top,
+        ]);
+    }
+
+    let widths = (0..rows[0].len())
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_reader;
+
+    #[test]
+    fn test_process_csv_stats() -> Result<()> {
+        let mut reader = get_reader("assets/juventus.csv")?;
+        let stats = process_csv_stats(&mut reader, &CsvReaderConfig::default(), 3)?;
+        assert_eq!(stats.len(), 5);
+
+        let kit = &stats[4];
+        assert_eq!(kit.column, "Kit Number");
+        assert_eq!(kit.ty, "int");
+        assert_eq!((kit.count, kit.nulls, kit.distinct), (27, 0, 27));
+        assert_eq!((kit.min, kit.max), (Some(1.0), Some(77.0)));
+
+        let nationality = &stats[3];
+        assert_eq!(nationality.ty, "string");
+        assert_eq!(nationality.mean, None);
+        assert_eq!(
+            nationality.top[0],
+            TopValue {
+                value: "Italy".to_string(),
+                count: 8
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_types() -> Result<()> {
+        let data = "a,b,c,d\n1,1.5,true,\n2,3,false,\n";
+        let stats = process_csv_stats(&mut data.as_bytes(), &CsvReaderConfig::default(), 5)?;
+        let types = stats.iter().map(|s| s.ty.as_str()).collect::<Vec<_>>();
+        assert_eq!(types, ["int", "float", "bool", "null"]);
+        assert_eq!(stats[1].mean, Some(2.25));
+        assert_eq!(stats[3].nulls, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_approx() -> Result<()> {
+        let data = (0..MAX_EXACT_DISTINCT + 10).fold("id,team\n".to_string(), |acc, i| {
+            acc + &format!("{},Juventus\n", i)
+        });
+        let stats = process_csv_stats(&mut data.as_bytes(), &CsvReaderConfig::default(), 1)?;
+        assert!(stats[0].distinct_approx && stats[0].top_approx);
+        assert!(!stats[1].distinct_approx && !stats[1].top_approx);
+
+        let mut buf = Vec::new();
+        write_csv_stats_table(&mut buf, &stats)?;
+        let table = String::from_utf8(buf)?;
+        assert!(table.contains("0 (~1)"));
+        assert!(table.contains(&format!("Juventus ({})", MAX_EXACT_DISTINCT + 10)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..200_000 {
+            hll.insert(&i.to_string());
+        }
+        let count = hll.count() as f64;
+        assert!((count - 200_000.0).abs() / 200_000.0 < 0.05);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use anyhow::Result;
+use csv::StringRecord;
+use serde_json::{Map, Value};
+
+use crate::CsvSchema;
+
+/// 转换时对列的选择、重命名、对行的过滤，以及分组和嵌套输出
+#[derive(Debug, Clone, Default)]
+pub struct CsvTransform {
+    pub select: Vec<String>,
+    pub rename: HashMap<String, String>,
+    pub filters: Vec<RowFilter>,
+    pub group_by: Option<String>,
+    // 将 address.city 这样的列名展开为嵌套对象
+    pub nest: bool,
+}
+
+/// 形如 "Nationality=Italy"、"Kit Number>10"、"Name contains Bon" 的行过滤条件
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowFilter {
+    pub column: String,
+    pub op: FilterOp,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Contains,
+}
+
+/// 根据表头计算出的列下标，避免每行都按列名查找
+#[derive(Debug)]
+pub struct CsvProjection {
+    // (下标, 原列名, 输出的列名)
+    columns: Vec<(usize, String, String)>,
+    filters: Vec<(usize, RowFilter)>,
+    group: Option<usize>,
+    nest: bool,
+}
+
+impl CsvTransform {
+    pub fn projection(&self, headers: &StringRecord) -> Result<CsvProjection> {
+        let index_of = |column: &str| column_index(headers, column);
+
+        let selected = match self.select.is_empty() {
+            true => headers.iter().map(|h| h.to_string()).collect(),
+            false => self.select.clone(),
+        };
+        let columns = selected
+            .into_iter()
+            .map(|column| {
+                let idx = index_of(&column)?;
+                let name = self.rename.get(&column).unwrap_or(&column).clone();
+                Ok((idx, column, name))
+            })
+            .collect::<Result<_>>()?;
+
+        for column in self.rename.keys() {
+            index_of(column)?;
+        }
+
+        let filters = self
+            .filters
+            .iter()
+            .map(|filter| Ok((index_of(&filter.column)?, filter.clone())))
+            .collect::<Result<_>>()?;
+
+        let group = self.group_by.as_deref().map(index_of).transpose()?;
+
+        Ok(CsvProjection {
+            columns,
+            filters,
+            group,
+            nest: self.nest,
+        })
+    }
+}
+
+impl CsvProjection {
+    /// 所有过滤条件都满足时返回 true
+    pub fn matches(&self, record: &StringRecord) -> bool {
+        self.filters
+            .iter()
+            .all(|(idx, filter)| filter.matches(record.get(*idx).unwrap_or_default()))
+    }
+
+    /// 分组列的下标
+    pub fn group_index(&self) -> Option<usize> {
+        self.group
+    }
+
+    pub fn to_value(&self, record: &StringRecord, schema: &CsvSchema) -> Result<Value> {
+        let row = self
+            .columns
+            .iter()
+            .filter_map(|(idx, column, name)| record.get(*idx).map(|field| (column, name, field)))
+            .map(|(column, name, field)| {
+                Ok((name.clone(), schema.convert_in(record, column, field)?))
+            })
+            .collect::<Result<Map<String, Value>>>()?;
+
+        match self.nest {
+            true => Ok(Value::Object(nest_object(row)?)),
+            false => Ok(Value::Object(row)),
+        }
+    }
+}
+
+/// 列名在表头中的下标
+pub fn column_index(headers: &StringRecord, column: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == column)
+        .ok_or_else(|| anyhow::anyhow!("column \"{}\" not found", column))
+}
+
+/// 按 . 拆分列名，生成嵌套的对象，如 address.city -> {"address": {"city": ...}}
+pub fn nest_object(row: Map<String, Value>) -> Result<Map<String, Value>> {
+    let mut ret = Map::new();
+    for (key, value) in row {
+        let mut parts = key.split('.').collect::<Vec<_>>();
+        let last = parts.pop().unwrap_or_default();
+
+        let mut current = &mut ret;
+        for part in parts {
+            let entry = current
+                .entry(part)
+                .or_insert_with(|| Value::Object(Map::new()));
+            current = match entry {
+                Value::Object(obj) => obj,
+                _ => anyhow::bail!("column \"{}\" conflicts with \"{}\"", key, part),
+            };
+        }
+        if current.insert(last.to_string(), value).is_some() {
+            anyhow::bail!("column \"{}\" conflicts with another column", key);
+        }
+    }
+    Ok(ret)
+}
+
+impl RowFilter {
+    pub fn matches(&self, field: &str) -> bool {
+        match self.op {
+            FilterOp::Eq => field == self.value,
+            FilterOp::Ne => field != self.value,
+            FilterOp::Lt => compare(field, &self.value) == Ordering::Less,
+            FilterOp::Gt => compare(field, &self.value) == Ordering::Greater,
+            FilterOp::Contains => field.contains(&self.value),
+        }
+    }
+}
+
+/// 两边都是数字时按数值比较，否则按字符串比较
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+impl FromStr for RowFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (column, op, value) = if let Some((column, value)) = s.split_once(" contains ") {
+            (column, FilterOp::Contains, value)
+        } else {
+            let idx = s
+                .find(['!', '=', '<', '>'])
+                .ok_or_else(|| anyhow::anyhow!("invalid filter: {}", s))?;
+            let (column, rest) = s.split_at(idx);
+            match rest.split_at(if rest.starts_with("!=") { 2 } else { 1 }) {
+                ("!=", value) => (column, FilterOp::Ne, value),
+                ("=", value) => (column, FilterOp::Eq, value),
+                ("<", value) => (column, FilterOp::Lt, value),
+                (">", value) => (column, FilterOp::Gt, value),
+                _ => anyhow::bail!("invalid filter: {}", s),
+            }
+        };
+
+        let column = column.trim();
+        if column.is_empty() {
+            anyhow::bail!("invalid filter: {}", s);
+        }
+        Ok(Self {
+            column: column.to_string(),
+            op,
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_row_filter() -> Result<()> {
+        let filter: RowFilter = "Nationality=Italy".parse()?;
+        assert_eq!(filter.column, "Nationality");
+        assert_eq!(filter.op, FilterOp::Eq);
+        assert_eq!(filter.value, "Italy");
+
+        let filter: RowFilter = "Kit Number != 1".parse()?;
+        assert_eq!(
+            (filter.column.as_str(), filter.op),
+            ("Kit Number", FilterOp::Ne)
+        );
+        assert_eq!("Kit Number<10".parse::<RowFilter>()?.op, FilterOp::Lt);
+        assert_eq!("Kit Number>10".parse::<RowFilter>()?.op, FilterOp::Gt);
+
+        let filter: RowFilter = "DOB contains 1990".parse()?;
+        assert_eq!(
+            (filter.column.as_str(), filter.op),
+            ("DOB", FilterOp::Contains)
+        );
+        assert_eq!(filter.value, "1990");
+
+        assert!("Nationality".parse::<RowFilter>().is_err());
+        assert!("=Italy".parse::<RowFilter>().is_err());
+        assert!("Kit Number!10".parse::<RowFilter>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_filter_matches() -> Result<()> {
+        assert!("Kit Number>9".parse::<RowFilter>()?.matches("10"));
+        assert!(!"Kit Number<9".parse::<RowFilter>()?.matches("10"));
+        assert!("Name<b".parse::<RowFilter>()?.matches("a"));
+        assert!("Name contains uff".parse::<RowFilter>()?.matches("Buffon"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_projection() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Nationality", "Kit Number"]);
+        let transform = CsvTransform {
+            select: vec!["Kit Number".to_string(), "Name".to_string()],
+            rename: HashMap::from([("Kit Number".to_string(), "kit".to_string())]),
+            filters: vec!["Nationality=Italy".parse()?],
+            ..Default::default()
+        };
+        let projection = transform.projection(&headers)?;
+        let schema = CsvSchema {
+            infer: true,
+            ..Default::default()
+        };
+
+        let record = StringRecord::from(vec!["Buffon", "Italy", "77"]);
+        assert!(projection.matches(&record));
+        let value = projection.to_value(&record, &schema)?;
+        assert_eq!(value, json!({"kit": 77, "Name": "Buffon"}));
+        assert_eq!(
+            value.as_object().unwrap().keys().collect::<Vec<_>>(),
+            ["kit", "Name"]
+        );
+
+        let record = StringRecord::from(vec!["Szczesny", "Poland", "1"]);
+        assert!(!projection.matches(&record));
+
+        let transform = CsvTransform {
+            select: vec!["Age".to_string()],
+            ..Default::default()
+        };
+        assert!(transform.projection(&headers).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nest_object() -> Result<()> {
+        let row = json!({"name": "Buffon", "address.city": "Turin", "address.zip": "10100"});
+        let Value::Object(row) = row else {
+            unreachable!()
+        };
+        let nested = nest_object(row)?;
+        assert_eq!(
+            Value::Object(nested),
+            json!({"name": "Buffon", "address": {"city": "Turin", "zip": "10100"}})
+        );
+
+        let row = json!({"address": "Turin", "address.city": "Turin"});
+        let Value::Object(row) = row else {
+            unreachable!()
+        };
+        assert!(nest_object(row).is_err());
+        Ok(())
+    }
+}
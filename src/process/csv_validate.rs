@@ -0,0 +1,333 @@
+use std::{fmt, io::Read};
+
+use anyhow::Result;
+use csv::StringRecord;
+use jsonschema::{paths::LocationSegment, Validator};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::csv_convert::Player;
+use crate::{ColumnType, CsvReaderConfig, CsvSchema};
+
+// 内置的 player 校验要求的列，和 Player 的字段一一对应
+const PLAYER_COLUMNS: &[&str] = &["Name", "Position", "DOB", "Nationality", "Kit Number"];
+
+type TypedCheck = fn(&StringRecord, &StringRecord, u64) -> Vec<RowError>;
+
+/// 校验规则：列类型声明或内置的结构体，以及可选的 JSON Schema
+pub struct CsvValidator {
+    types: CsvSchema,
+    declared: Vec<String>,
+    typed: Option<TypedCheck>,
+    json_schema: Option<Validator>,
+}
+
+/// 一条校验错误，column 为 None 时表示整行的错误（如缺少必填列）
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub line: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    pub rows: usize,
+    pub failed_rows: usize,
+    // 表头的错误不计入 failed_rows
+    pub header_errors: usize,
+    pub errors: Vec<RowError>,
+}
+
+impl CsvValidator {
+    /// types 中的列必须存在且能转换为对应类型
+    pub fn new(types: CsvSchema) -> Self {
+        let mut declared = types.columns.keys().cloned().collect::<Vec<_>>();
+        declared.sort();
+        Self {
+            types,
+            declared,
+            typed: None,
+            json_schema: None,
+        }
+    }
+
+    /// 把每一行反序列化为 Player 结构体来校验
+    pub fn player() -> Self {
+        Self {
+            declared: PLAYER_COLUMNS.iter().map(|c| c.to_string()).collect(),
+            typed: Some(validate_typed::<Player>),
+            ..Self::new(CsvSchema::default())
+        }
+    }
+
+    /// 根据 JSON Schema 中 properties 的 type 转换字段后再校验，types 中的声明优先
+    pub fn with_json_schema(mut self, schema: &Value) -> Result<Self> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| anyhow::anyhow!("invalid json schema: {}", e))?;
+
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (column, property) in properties {
+                let ty = match property.get("type").and_then(Value::as_str) {
+                    Some("integer") => ColumnType::Int,
+                    Some("number") => ColumnType::Float,
+                    Some("boolean") => ColumnType::Bool,
+                    _ => ColumnType::String,
+                };
+                self.types.columns.entry(column.clone()).or_insert(ty);
+            }
+        }
+
+        self.json_schema = Some(validator);
+        Ok(self)
+    }
+
+    // json schema 中的列由 required 校验，这里只检查显式声明的列
+    fn validate_headers(&self, headers: &StringRecord) -> Vec<RowError> {
+        self.declared
+            .iter()
+            .filter(|column| !headers.iter().any(|h| h == column.as_str()))
+            .map(|column| RowError {
+                line: 1,
+                column: Some(column.clone()),
+                message: "column not found in header".to_string(),
+            })
+            .collect()
+    }
+
+    fn validate_record(&self, headers: &StringRecord, record: &StringRecord) -> Vec<RowError> {
+        let line = record.position().map_or(0, |p| p.line());
+        let mut errors = Vec::new();
+        let mut row = serde_json::Map::new();
+
+        for (column, field) in headers.iter().zip(record.iter()) {
+            match self.types.convert(column, field) {
+                Ok(value) => {
+                    row.insert(column.to_string(), value);
+                }
+                Err(e) => {
+                    errors.push(RowError {
+                        line,
+                        column: Some(column.to_string()),
+                        message: format!("{}: {:?}", e, field),
+                    });
+                    // 保留原始字符串，避免 json schema 的 required 再报告一次
+                    row.insert(column.to_string(), Value::String(field.to_string()));
+                }
+            }
+        }
+        if headers.len() != record.len() {
+            errors.push(RowError {
+                line,
+                column: None,
+                message: format!("expected {} fields, got {}", headers.len(), record.len()),
+            });
+        }
+
+        if let Some(typed) = self.typed {
+            for error in typed(headers, record, line) {
+                if !errors.iter().any(|e| e.column == error.column) {
+                    errors.push(error);
+                }
+            }
+        }
+
+        if let Some(validator) = &self.json_schema {
+            let row = Value::Object(row);
+            for e in validator.iter_errors(&row) {
+                let column = e.instance_path().iter().next().map(|s| match s {
+                    LocationSegment::Property(p) => p.to_string(),
+                    LocationSegment::Index(i) => i.to_string(),
+                });
+                // 类型转换失败的列已经报告过
+                if column.is_some() && errors.iter().any(|err| err.column == column) {
+                    continue;
+                }
+                errors.push(RowError {
+                    line,
+                    column,
+                    message: e.to_string(),
+                });
+            }
+        }
+        errors
+    }
+}
+
+pub fn process_csv_validate(
+    reader: &mut dyn Read,
+    config: &CsvReaderConfig,
+    validator: &CsvValidator,
+) -> Result<ValidationReport> {
+    // 行数不一致也作为校验错误报告，而不是直接失败
+    let config = CsvReaderConfig {
+        flexible: true,
+        ..config.clone()
+    };
+    let mut reader = config.reader(reader)?;
+    let headers = config.headers(&mut reader)?;
+
+    let errors = validator.validate_headers(&headers);
+    let mut report = ValidationReport {
+        header_errors: errors.len(),
+        errors,
+        ..Default::default()
+    };
+
+    let mut record = StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let errors = validator.validate_record(&headers, &record);
+        report.rows += 1;
+        if !errors.is_empty() {
+            report.failed_rows += 1;
+            report.errors.extend(errors);
+        }
+    }
+    Ok(report)
+}
+
+// 缺少的列已经在表头中报告，这里只报告能定位到列的错误
+fn validate_typed<T: DeserializeOwned>(
+    headers: &StringRecord,
+    record: &StringRecord,
+    line: u64,
+) -> Vec<RowError> {
+    match record.deserialize::<T>(Some(headers)) {
+        Ok(_) => Vec::new(),
+        Err(e) => match e.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => err
+                .field()
+                .and_then(|i| headers.get(i as usize))
+                .map(|column| RowError {
+                    line,
+                    column: Some(column.to_string()),
+                    message: err.kind().to_string(),
+                })
+                .into_iter()
+                .collect(),
+            _ => vec![RowError {
+                line,
+                column: None,
+                message: e.to_string(),
+            }],
+        },
+    }
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(
+                f,
+                "line {}, column \"{}\": {}",
+                self.line, column, self.message
+            ),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_reader;
+
+    #[test]
+    fn test_validate_columns() -> Result<()> {
+        let data = "Name,Kit Number\nBuffon,77\nChiellini,three\nDe Ligt\n";
+        let validator = CsvValidator::new("Kit Number=int,Age=int".parse()?);
+        let report = process_csv_validate(
+            &mut data.as_bytes(),
+            &CsvReaderConfig::default(),
+            &validator,
+        )?;
+
+        assert!(!report.is_valid());
+        assert_eq!(report.rows, 3);
+        assert_eq!(report.failed_rows, 2);
+        assert_eq!(report.header_errors, 1);
+        let errors = report
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors[0],
+            "line 1, column \"Age\": column not found in header"
+        );
+        assert!(errors[1].starts_with("line 3, column \"Kit Number\": invalid digit"));
+        assert_eq!(errors[2], "line 4: expected 2 fields, got 1");
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_json_schema() -> Result<()> {
+        let schema: Value = serde_json::from_slice(&std::fs::read("fixtures/player.schema.json")?)?;
+        let validator = CsvValidator::new(CsvSchema::default()).with_json_schema(&schema)?;
+
+        let mut reader = get_reader("assets/juventus.csv")?;
+        let report = process_csv_validate(&mut reader, &CsvReaderConfig::default(), &validator)?;
+        assert!(report.is_valid());
+        assert_eq!(report.rows, 27);
+
+        let data = "Name,Position,DOB,Nationality,Kit Number\nBuffon,Goalkeeper,,Italy,300\n";
+        let report = process_csv_validate(
+            &mut data.as_bytes(),
+            &CsvReaderConfig::default(),
+            &validator,
+        )?;
+        let columns = report
+            .errors
+            .iter()
+            .map(|e| e.column.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(columns, [Some("DOB"), Some("Kit Number")]);
+
+        // 类型转换失败的列不会再被 required 报告一次
+        let data = "Name,Position,DOB,Nationality,Kit Number\nBuffon,Goalkeeper,1978,Italy,one\n";
+        let report = process_csv_validate(
+            &mut data.as_bytes(),
+            &CsvReaderConfig::default(),
+            &validator,
+        )?;
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].column.as_deref(), Some("Kit Number"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_player() -> Result<()> {
+        let mut reader = get_reader("assets/juventus.csv")?;
+        let validator = CsvValidator::player();
+        let report = process_csv_validate(&mut reader, &CsvReaderConfig::default(), &validator)?;
+        assert!(report.is_valid());
+        assert_eq!(report.rows, 27);
+
+        let data = "Name,Position,DOB,Kit Number\nBuffon,Goalkeeper,1978,300\n";
+        let report = process_csv_validate(
+            &mut data.as_bytes(),
+            &CsvReaderConfig::default(),
+            &validator,
+        )?;
+        assert_eq!(report.header_errors, 1);
+        assert_eq!(report.failed_rows, 1);
+        let errors = report
+            .errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors[0],
+            "line 1, column \"Nationality\": column not found in header"
+        );
+        assert!(errors[1].starts_with("line 2, column \"Kit Number\": "));
+        assert_eq!(errors.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, GBK, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+use crate::cli::InputEncoding;
+
+// 自动检测编码时读取的字节数
+const SNIFF_LEN: usize = 64 * 1024;
+
+/// 将输入按指定编码转码为 utf-8，开头的 BOM 会被去掉；存在 BOM 时以 BOM 为准
+pub fn decode_reader<'a>(
+    mut reader: impl Read + 'a,
+    encoding: InputEncoding,
+) -> Result<Box<dyn Read + 'a>> {
+    let encoding = match encoding {
+        // utf-8 不需要转码，只检查 BOM
+        InputEncoding::Utf8 => None,
+        InputEncoding::Gbk => Some(GBK),
+        // latin1 按 WHATWG 的规范映射为 windows-1252，它是 latin1 可打印字符的超集
+        InputEncoding::Latin1 => Some(WINDOWS_1252),
+        InputEncoding::Utf16Le => Some(UTF_16LE),
+        InputEncoding::Utf16Be => Some(UTF_16BE),
+        InputEncoding::Auto => {
+            let mut prefix = Vec::with_capacity(SNIFF_LEN);
+            (&mut reader)
+                .take(SNIFF_LEN as u64)
+                .read_to_end(&mut prefix)?;
+            let encoding = detect_encoding(&prefix, prefix.len() < SNIFF_LEN);
+            let reader = Cursor::new(prefix).chain(reader);
+            return Ok(Box::new(decoder(Some(encoding), reader)));
+        }
+    };
+    Ok(Box::new(decoder(encoding, reader)))
+}
+
+/// 优先根据 BOM 判断编码，没有 BOM 时使用 chardetng 根据内容推测
+pub fn detect_encoding(prefix: &[u8], complete: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(prefix) {
+        return encoding;
+    }
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(prefix, complete);
+    detector.guess(None, Utf8Detection::Allow)
+}
+
+fn decoder<'a>(encoding: Option<&'static Encoding>, reader: impl Read + 'a) -> impl Read + 'a {
+    DecodeReaderBytesBuilder::new()
+        .encoding(encoding)
+        .bom_override(true)
+        .strip_bom(true)
+        .build(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(data: &[u8], encoding: InputEncoding) -> Result<String> {
+        let mut content = String::new();
+        decode_reader(data, encoding)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn test_decode_reader() -> Result<()> {
+        assert_eq!(
+            decode(b"\xef\xbb\xbfName\n", InputEncoding::Utf8)?,
+            "Name\n"
+        );
+        assert_eq!(
+            decode(b"Name\nJos\xe9\n", InputEncoding::Latin1)?,
+            "Name\nJosé\n"
+        );
+        assert_eq!(decode(b"\xc3\xfb\xd7\xd6\n", InputEncoding::Gbk)?, "名字\n");
+        assert_eq!(decode(b"N\0a\0\n\0", InputEncoding::Utf16Le)?, "Na\n");
+        // BOM 优先于指定的编码
+        assert_eq!(decode(b"\xff\xfeN\0a\0", InputEncoding::Gbk)?, "Na");
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_encoding() -> Result<()> {
+        assert_eq!(decode(b"\xfe\xff\0N\0a", InputEncoding::Auto)?, "Na");
+        assert_eq!(
+            decode("名字,年龄\n".as_bytes(), InputEncoding::Auto)?,
+            "名字,年龄\n"
+        );
+
+        let (latin1, _, _) = WINDOWS_1252.encode("Name,City\nJosé,São Paulo\nMüller,München\n");
+        assert_eq!(detect_encoding(&latin1, true), WINDOWS_1252);
+        let (gbk, _, _) = GBK.encode("姓名,城市\n张三,北京\n李四,上海\n王五,广州\n");
+        assert_eq!(detect_encoding(&gbk, true), GBK);
+        Ok(())
+    }
+}
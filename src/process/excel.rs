@@ -0,0 +1,106 @@
+use std::io::{Cursor, Read, Write};
+
+use anyhow::Result;
+use calamine::{open_workbook_auto_from_rs, Data, DataType, Reader};
+use csv::{Position, StringRecord};
+
+use crate::{cli::OutputFormat, write_records, CsvSchema, CsvTransform};
+
+/// 读取 excel 中的一个 sheet，之后和 csv 一样转换输出
+pub fn process_excel(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    format: OutputFormat,
+    sheet: Option<&str>,
+    has_headers: bool,
+    schema: &CsvSchema,
+    transform: &CsvTransform,
+) -> Result<()> {
+    let (headers, records) = read_sheet(reader, sheet, has_headers)?;
+    write_records(
+        &headers,
+        records.into_iter().map(Ok),
+        writer,
+        format,
+        schema,
+        transform,
+    )
+}
+
+/// 将 sheet 读取为表头和字符串记录，sheet 为 None 时读取第一个 sheet
+pub fn read_sheet(
+    reader: &mut dyn Read,
+    sheet: Option<&str>,
+    has_headers: bool,
+) -> Result<(StringRecord, Vec<StringRecord>)> {
+    // calamine 需要 Read + Seek，stdin 等输入先读入内存
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(buf))?;
+
+    let names = workbook.sheet_names();
+    let name = match sheet {
+        Some(name) if names.iter().any(|n| n == name) => name.to_string(),
+        Some(name) => anyhow::bail!(
+            "sheet \"{}\" not found, available sheets: {}",
+            name,
+            names.join(", ")
+        ),
+        None => names
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("workbook has no sheets"))?,
+    };
+    let range = workbook.worksheet_range(&name)?;
+
+    // 记录的行号和 excel 中显示的行号一致，转换出错时方便定位
+    let start = range.start().map_or(0, |(row, _)| row as u64);
+    let mut rows = range.rows().enumerate().map(|(i, row)| {
+        let mut record = row.iter().map(cell_to_string).collect::<StringRecord>();
+        let mut position = Position::new();
+        position.set_line(start + i as u64 + 1);
+        record.set_position(Some(position));
+        record
+    });
+
+    let headers = match has_headers {
+        true => rows.next().unwrap_or_default(),
+        false => (0..range.width()).map(|i| format!("col{}", i)).collect(),
+    };
+    Ok((headers, rows.collect()))
+}
+
+// 日期输出为 ISO 8601 格式，其余单元格使用 calamine 的默认格式
+fn cell_to_string(cell: &Data) -> String {
+    match (cell, cell.as_datetime()) {
+        (Data::DateTime(dt), Some(datetime)) if dt.as_f64().fract() == 0.0 => {
+            datetime.format("%Y-%m-%d").to_string()
+        }
+        (Data::DateTime(_), Some(datetime)) => datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        _ => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_reader;
+
+    #[test]
+    fn test_read_sheet() -> Result<()> {
+        let mut reader = get_reader("fixtures/players.xlsx")?;
+        let (headers, records) = read_sheet(&mut reader, None, true)?;
+        assert_eq!(headers, vec!["Name", "Kit Number", "DOB"]);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], vec!["Wojciech Szczesny", "1", "1990-04-18"]);
+        assert_eq!(records[2].position().map(|p| p.line()), Some(4));
+
+        let mut reader = get_reader("fixtures/players.xlsx")?;
+        let (_, records) = read_sheet(&mut reader, Some("Legends"), true)?;
+        assert_eq!(records[0], vec!["Alessandro Del Piero", "10"]);
+
+        let mut reader = get_reader("fixtures/players.xlsx")?;
+        assert!(read_sheet(&mut reader, Some("Milan"), true).is_err());
+        Ok(())
+    }
+}
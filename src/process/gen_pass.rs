@@ -1,4 +1,7 @@
-use rand::seq::SliceRandom;
+use rand::{rngs::OsRng, seq::SliceRandom, CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use zxcvbn::zxcvbn;
 
 // 为了避免相近符号的混淆，移除 O 和 0
 const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
@@ -6,46 +9,395 @@ const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
 const NUMBER: &[u8] = b"123456789";
 const SYMBOL: &[u8] = b"!@#$%^&*_";
 
-pub fn process_genpass(
-    length: u8,
-    uppercase: String,
-    lowercase: String,
-    number: String,
-    symbol: String,
+// EFF 的长词表，共 7776 个单词，每行的格式为 "11111\tabacus"
+const EFF_WORDLIST: &str = include_str!("../../assets/eff_large_wordlist.txt");
+
+/// 随机密码的生成规则，charset 不为空时使用自定义字符集代替内置的字符类别
+#[derive(Debug, Clone)]
+pub struct GenPassConfig {
+    pub length: u8,
+    pub uppercase: bool,
+    pub lowercase: bool,
+    pub number: bool,
+    pub symbol: bool,
+    pub charset: Option<String>,
+    // 额外排除的字符，比如 l1I
+    pub exclude: String,
+    pub min_digits: u8,
+    pub min_symbols: u8,
+    pub start_with_letter: bool,
+}
+
+/// zxcvbn 对密码强度的评估，crack_time 为离线慢哈希（每秒 1 万次）场景下的破解时间
+#[derive(Debug, Serialize)]
+pub struct PasswordStrength {
+    pub password: String,
+    pub score: u8,
+    pub entropy_bits: f64,
+    pub crack_time: String,
+}
+
+/// 生成密码使用的随机数生成器，只接受密码学安全的实现
+pub trait PasswordRng: RngCore + CryptoRng {}
+
+impl<R: RngCore + CryptoRng + ?Sized> PasswordRng for R {}
+
+/// 默认使用操作系统提供的 OsRng；指定 seed 时使用 ChaCha20，结果可以复现，只能用于测试
+pub fn password_rng(seed: Option<u64>) -> Box<dyn PasswordRng> {
+    match seed {
+        Some(seed) => Box::new(ChaCha20Rng::seed_from_u64(seed)),
+        None => Box::new(OsRng),
+    }
+}
+
+/// 校验后的字符集：pool 为所有可选字符，required 中的每一组至少要选出对应数量的字符
+struct Alphabet {
+    pool: Vec<char>,
+    required: Vec<(Vec<char>, usize)>,
+}
+
+impl Default for GenPassConfig {
+    fn default() -> Self {
+        Self {
+            length: 16,
+            uppercase: true,
+            lowercase: true,
+            number: false,
+            symbol: false,
+            charset: None,
+            exclude: String::new(),
+            min_digits: 0,
+            min_symbols: 0,
+            start_with_letter: false,
+        }
+    }
+}
+
+impl GenPassConfig {
+    fn alphabet(&self) -> anyhow::Result<Alphabet> {
+        if self.length == 0 {
+            anyhow::bail!("password length must be greater than 0");
+        }
+        let keep = |chars: &mut dyn Iterator<Item = char>| {
+            let mut ret = Vec::new();
+            for c in chars {
+                if !self.exclude.contains(c) && !ret.contains(&c) {
+                    ret.push(c);
+                }
+            }
+            ret
+        };
+
+        let mut required = Vec::new();
+        let pool = match &self.charset {
+            Some(charset) => {
+                let pool = keep(&mut charset.chars());
+                let digits = pool.iter().copied().filter(char::is_ascii_digit).collect();
+                let symbols = pool
+                    .iter()
+                    .copied()
+                    .filter(|c| !c.is_alphanumeric())
+                    .collect();
+                for (name, chars, min) in [
+                    ("digits", digits, self.min_digits),
+                    ("symbols", symbols, self.min_symbols),
+                ] {
+                    if min > 0 {
+                        required.push((name, chars, min as usize));
+                    }
+                }
+                pool
+            }
+            None => {
+                // 指定了 min_digits/min_symbols 时自动包含数字/符号
+                let classes = [
+                    ("uppercase letters", UPPER, self.uppercase, 1),
+                    ("lowercase letters", LOWER, self.lowercase, 1),
+                    (
+                        "numbers",
+                        NUMBER,
+                        self.number || self.min_digits > 0,
+                        self.min_digits.max(1),
+                    ),
+                    (
+                        "symbols",
+                        SYMBOL,
+                        self.symbol || self.min_symbols > 0,
+                        self.min_symbols.max(1),
+                    ),
+                ];
+                let mut pool = Vec::new();
+                for (name, class, enabled, min) in classes {
+                    if enabled {
+                        let chars = keep(&mut class.iter().map(|&b| b as char));
+                        pool.extend_from_slice(&chars);
+                        required.push((name, chars, min as usize));
+                    }
+                }
+                pool
+            }
+        };
+
+        if pool.is_empty() {
+            anyhow::bail!("no characters to generate the password from, enable at least one character class or use a non empty --charset");
+        }
+        if self.start_with_letter && !pool.iter().any(|c| c.is_alphabetic()) {
+            anyhow::bail!("the password must start with a letter but no letters are available");
+        }
+        if let Some((name, _, _)) = required.iter().find(|(_, chars, _)| chars.is_empty()) {
+            anyhow::bail!("no {} left to choose from", name);
+        }
+        let total = required.iter().map(|(_, _, min)| min).sum::<usize>();
+        if total > self.length as usize {
+            anyhow::bail!(
+                "password length {} is too short for {} required characters",
+                self.length,
+                total
+            );
+        }
+
+        // 必需的字符已经占满长度且其中没有字母时，无法以字母开头
+        let has_letters = required
+            .iter()
+            .any(|(_, chars, _)| chars.iter().all(|c| c.is_alphabetic()));
+        if self.start_with_letter && total == self.length as usize && !has_letters {
+            anyhow::bail!(
+                "password length {} is too short to start with a letter",
+                self.length
+            );
+        }
+
+        Ok(Alphabet {
+            pool,
+            required: required
+                .into_iter()
+                .map(|(_, chars, min)| (chars, min))
+                .collect(),
+        })
+    }
+}
+
+pub fn process_genpass<R: PasswordRng + ?Sized>(
+    config: &GenPassConfig,
+    rng: &mut R,
 ) -> anyhow::Result<String> {
-    let mut rng = rand::thread_rng();
+    let alphabet = config.alphabet()?;
 
-    // 保存生成的密码
+    // 保存生成的密码，先放入每一类必须包含的字符
     let mut password = Vec::new();
+    for (chars, min) in &alphabet.required {
+        for _ in 0..*min {
+            password.push(*chars.choose(rng).expect("checked in alphabet"));
+        }
+    }
+
+    // 其余字符从所有可选字符中随机选择
+    while password.len() < config.length as usize {
+        password.push(*alphabet.pool.choose(rng).expect("checked in alphabet"));
+    }
+
+    // 要求以字母开头但没有选中字母时，把一个非必需的字符换成字母
+    if config.start_with_letter && !password.iter().any(|c| c.is_alphabetic()) {
+        let letters = alphabet
+            .pool
+            .iter()
+            .filter(|c| c.is_alphabetic())
+            .collect::<Vec<_>>();
+        let last = password.len() - 1;
+        password[last] = **letters.choose(rng).expect("checked in alphabet");
+    }
+
+    // 因为前面部分是固定模式生成的比如第一个永远都是大写...，所以需要对密码进行打乱
+    password.shuffle(rng);
+
+    // 和随机位置上的一个字母交换，不影响每一类字符的数量
+    if config.start_with_letter && !password[0].is_alphabetic() {
+        let letters = (0..password.len())
+            .filter(|&i| password[i].is_alphabetic())
+            .collect::<Vec<_>>();
+        let i = *letters.choose(rng).expect("a letter is added above");
+        password.swap(0, i);
+    }
+
+    Ok(password.into_iter().collect())
+}
+
+pub fn estimate_strength(password: &str) -> PasswordStrength {
+    let estimate = zxcvbn(password, &[]);
+    let bits = estimate.guesses_log10() * 10f64.log2();
+    PasswordStrength {
+        password: password.to_string(),
+        score: estimate.score().into(),
+        entropy_bits: (bits * 100.0).round() / 100.0,
+        crack_time: estimate
+            .crack_times()
+            .offline_slow_hashing_1e4_per_second()
+            .to_string(),
+    }
+}
+
+/// 生成 diceware 风格的口令，digit 为 true 时在随机一个单词后附加一位数字
+pub fn process_genpass_words<R: PasswordRng + ?Sized>(
+    words: u8,
+    separator: &str,
+    capitalize: bool,
+    digit: bool,
+    rng: &mut R,
+) -> anyhow::Result<String> {
+    if words == 0 {
+        anyhow::bail!("words must be greater than 0");
+    }
+    let list = eff_wordlist();
+
+    let mut passphrase = (0..words)
+        .map(|_| {
+            let word = *list.choose(rng).expect("wordlist won't be empty");
+            match capitalize {
+                true => word[..1].to_ascii_uppercase() + &word[1..],
+                false => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>();
 
-    // 保存生成密码的字符，密码会从中随机选择
-    let mut chars = Vec::new();
+    if digit {
+        let idx = rng.gen_range(0..passphrase.len());
+        passphrase[idx].push(char::from(b'0' + rng.gen_range(0..10)));
+    }
+    Ok(passphrase.join(separator))
+}
+
+pub fn eff_wordlist() -> Vec<&'static str> {
+    EFF_WORDLIST
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(_, word)| word)
+        .collect()
+}
 
-    if uppercase.to_ascii_lowercase().eq("true") {
-        chars.extend_from_slice(UPPER);
-        password.push(*UPPER.choose(&mut rng).expect("UPPER won't be empty"));
+/// 口令的理论熵，分隔符和大小写是固定的，不增加熵
+pub fn passphrase_entropy(words: u8, digit: bool) -> f64 {
+    let mut bits = words as f64 * (eff_wordlist().len() as f64).log2();
+    if digit {
+        // 数字本身和它所在的单词位置
+        bits += 10f64.log2() + (words as f64).log2();
     }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_genpass() -> anyhow::Result<()> {
+        let config = GenPassConfig {
+            length: 12,
+            min_digits: 4,
+            min_symbols: 2,
+            exclude: "abc".to_string(),
+            ..Default::default()
+        };
+        let password = process_genpass(&config, &mut OsRng)?;
+        assert_eq!(password.chars().count(), 12);
+        assert!(password.chars().filter(char::is_ascii_digit).count() >= 4);
+        assert!(password.bytes().filter(|b| SYMBOL.contains(b)).count() >= 2);
+        assert!(!password.contains(['a', 'b', 'c']));
 
-    if lowercase.to_ascii_lowercase().eq("true") {
-        chars.extend_from_slice(LOWER);
-        password.push(*LOWER.choose(&mut rng).expect("LOWER won't be empty"));
+        let config = GenPassConfig {
+            charset: Some("01".to_string()),
+            min_digits: 1,
+            ..Default::default()
+        };
+        assert!(process_genpass(&config, &mut OsRng)?
+            .chars()
+            .all(|c| c == '0' || c == '1'));
+        Ok(())
     }
 
-    if number.to_ascii_lowercase().eq("true") {
-        chars.extend_from_slice(NUMBER);
-        password.push(*NUMBER.choose(&mut rng).expect("NUMBER won't be empty"));
+    #[test]
+    fn test_seeded_rng() -> anyhow::Result<()> {
+        let config = GenPassConfig {
+            number: true,
+            symbol: true,
+            ..Default::default()
+        };
+        let generate = |seed| process_genpass(&config, &mut *password_rng(Some(seed)));
+        assert_eq!(generate(42)?, generate(42)?);
+        assert_ne!(generate(42)?, generate(43)?);
+
+        let mut rng = password_rng(Some(7));
+        let first = process_genpass_words(4, "-", false, false, &mut *rng)?;
+        let second = process_genpass_words(4, "-", false, false, &mut *rng)?;
+        assert_ne!(first, second);
+        assert_eq!(
+            first,
+            process_genpass_words(4, "-", false, false, &mut *password_rng(Some(7)))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_strength() {
+        let strength = estimate_strength("password");
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.crack_time, "less than a second");
+
+        let strength = estimate_strength("correct-horse-battery-staple-9");
+        assert_eq!(strength.score, 4);
+        assert!(strength.entropy_bits > 40.0);
     }
 
-    if symbol.to_ascii_lowercase().eq("true") {
-        chars.extend_from_slice(SYMBOL);
-        password.push(*SYMBOL.choose(&mut rng).expect("SYMBOL won't be empty"));
+    #[test]
+    fn test_genpass_config_errors() {
+        let errors = [
+            GenPassConfig {
+                uppercase: false,
+                lowercase: false,
+                ..Default::default()
+            },
+            GenPassConfig {
+                length: 0,
+                ..Default::default()
+            },
+            GenPassConfig {
+                length: 4,
+                min_digits: 3,
+                ..Default::default()
+            },
+            GenPassConfig {
+                number: true,
+                exclude: "123456789".to_string(),
+                ..Default::default()
+            },
+            GenPassConfig {
+                charset: Some("abc".to_string()),
+                min_symbols: 1,
+                ..Default::default()
+            },
+        ];
+        for config in errors {
+            assert!(
+                process_genpass(&config, &mut OsRng).is_err(),
+                "{:?}",
+                config
+            );
+        }
     }
 
-    (0..(length - password.len() as u8)).for_each(|_| {
-        password.push(*chars.choose(&mut rng).expect("chars won't be empty"));
-    });
+    #[test]
+    fn test_process_genpass_words() -> anyhow::Result<()> {
+        assert_eq!(eff_wordlist().len(), 7776);
+
+        let passphrase = process_genpass_words(6, " ", true, true, &mut OsRng)?;
+        let words = passphrase.split(' ').collect::<Vec<_>>();
+        assert_eq!(words.len(), 6);
+        assert!(words
+            .iter()
+            .all(|w| w.starts_with(|c: char| c.is_ascii_uppercase())));
+        assert_eq!(passphrase.chars().filter(char::is_ascii_digit).count(), 1);
 
-    // 因为前面部分是固定模式生成的比如第一个永远都是小写...，所以需要对密码进行打乱
-    password.shuffle(&mut rng);
-    Ok(String::from_utf8(password)?)
+        assert!(process_genpass_words(0, "-", false, false, &mut OsRng).is_err());
+        assert!((passphrase_entropy(6, false) - 77.55).abs() < 0.01);
+        Ok(())
+    }
 }
@@ -0,0 +1,292 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use data_encoding::HEXLOWER;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{get_reader, HashAlgorithm, HashFormat};
+
+// 流式读取时每次读取的字节数
+const BUF_SIZE: usize = 64 * 1024;
+
+/// sha256sum -c 中每个文件的检查结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    // 文件不存在或者无法读取
+    Unreadable,
+}
+
+#[derive(Debug)]
+pub struct HashCheck {
+    pub file: String,
+    pub status: CheckStatus,
+}
+
+#[derive(Debug, Default)]
+pub struct HashCheckReport {
+    pub checks: Vec<HashCheck>,
+    // 格式不对或者摘要长度和算法不符的行数，这些行不参与校验
+    pub improperly_formatted: usize,
+}
+
+/// 计算文件的摘要，- 表示 stdin；blake3 对大文件使用 mmap 和 rayon 多线程计算
+pub fn process_hash(input: &str, algo: HashAlgorithm) -> Result<Vec<u8>> {
+    if algo == HashAlgorithm::Blake3 && input != "-" {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_mmap_rayon(input)?;
+        return Ok(hasher.finalize().as_bytes().to_vec());
+    }
+    hash_reader(&mut get_reader(input)?, algo)
+}
+
+/// 分块读取计算摘要，不会把整个输入读入内存
+pub fn hash_reader(reader: &mut dyn Read, algo: HashAlgorithm) -> Result<Vec<u8>> {
+    match algo {
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(reader)?;
+            Ok(hasher.finalize().as_bytes().to_vec())
+        }
+        HashAlgorithm::Sha256 => digest::<Sha256>(reader),
+        HashAlgorithm::Sha512 => digest::<Sha512>(reader),
+        HashAlgorithm::Sha1 => digest::<Sha1>(reader),
+        HashAlgorithm::Md5 => digest::<Md5>(reader),
+        HashAlgorithm::Xxh3 => {
+            let mut hasher = Xxh3::new();
+            read_chunks(reader, |chunk| hasher.update(chunk))?;
+            // 和 xxhsum 一样按大端输出
+            Ok(hasher.digest().to_be_bytes().to_vec())
+        }
+    }
+}
+
+pub fn encode_digest(digest: &[u8], format: HashFormat) -> String {
+    match format {
+        HashFormat::Hex => HEXLOWER.encode(digest),
+        HashFormat::Base64 => STANDARD.encode(digest),
+    }
+}
+
+/// sha256sum 格式的一行：摘要、两个空格、文件名；文件名中有反斜杠或换行时转义，并在行首加 \
+pub fn format_sum_line(digest: &str, file: &str) -> String {
+    if !file.contains(['\\', '\n', '\r']) {
+        return format!("{}  {}", digest, file);
+    }
+    let file = file
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\\{}  {}", digest, file)
+}
+
+/// 逐行检查 sha256sum 格式的校验文件，文件名相对于当前目录
+///
+/// 和 sha256sum -c 一样，摘要长度和 algo 不符的行算作格式错误而不是校验失败
+pub fn process_hash_check(
+    sums: &mut dyn Read,
+    algo: HashAlgorithm,
+    format: HashFormat,
+) -> Result<HashCheckReport> {
+    let digest_len = encode_digest(&vec![0; algo.digest_len()], format).len();
+    let mut report = HashCheckReport::default();
+    for line in BufReader::new(sums).lines() {
+        let line = line?;
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            continue;
+        }
+        let Some((expected, file)) =
+            parse_sum_line(line).filter(|(expected, _)| expected.len() == digest_len)
+        else {
+            report.improperly_formatted += 1;
+            continue;
+        };
+
+        let status = match process_hash(&file, algo) {
+            Ok(digest) => {
+                let actual = encode_digest(&digest, format);
+                // 十六进制不区分大小写
+                let matched = match format {
+                    HashFormat::Hex => actual.eq_ignore_ascii_case(expected),
+                    HashFormat::Base64 => actual == expected,
+                };
+                match matched {
+                    true => CheckStatus::Ok,
+                    false => CheckStatus::Failed,
+                }
+            }
+            Err(_) => CheckStatus::Unreadable,
+        };
+        report.checks.push(HashCheck { file, status });
+    }
+    Ok(report)
+}
+
+// 摘要和文件名之间是一个空格加上模式标记：空格表示文本模式，* 表示二进制模式
+fn parse_sum_line(line: &str) -> Option<(&str, String)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (digest, rest) = line.split_once(' ')?;
+    let file = rest.strip_prefix([' ', '*'])?;
+    if digest.is_empty() || file.is_empty() {
+        return None;
+    }
+    let file = match escaped {
+        true => unescape(file),
+        false => file.to_string(),
+    };
+    Some((digest, file))
+}
+
+fn unescape(file: &str) -> String {
+    let mut ret = String::with_capacity(file.len());
+    let mut chars = file.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => ret.push('\n'),
+                Some('r') => ret.push('\r'),
+                Some(c) => ret.push(c),
+                None => ret.push('\\'),
+            },
+            (c, false) => ret.push(c),
+        }
+    }
+    ret
+}
+
+fn digest<D: Digest>(reader: &mut dyn Read) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    read_chunks(reader, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn read_chunks(reader: &mut dyn Read, mut f: impl FnMut(&[u8])) -> Result<()> {
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => f(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_hash_reader() -> Result<()> {
+        let cases = [
+            (HashAlgorithm::Sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (HashAlgorithm::Sha512, "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"),
+            (HashAlgorithm::Sha1, "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            (HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+            (HashAlgorithm::Blake3, "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"),
+            (HashAlgorithm::Xxh3, "78af5f94892f3950"),
+        ];
+        for (algo, expected) in cases {
+            let digest = hash_reader(&mut "abc".as_bytes(), algo)?;
+            assert_eq!(digest.len(), algo.digest_len());
+            assert_eq!(
+                encode_digest(&digest, HashFormat::Hex),
+                expected,
+                "{}",
+                algo
+            );
+        }
+
+        let digest = hash_reader(&mut "abc".as_bytes(), HashAlgorithm::Sha256)?;
+        assert_eq!(
+            encode_digest(&digest, HashFormat::Base64),
+            "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_hash_file() -> Result<()> {
+        // 超过 16KiB 的文件 blake3 才会使用 mmap
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("big.bin");
+        let data = (0..200_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&path, &data)?;
+        let path = path.to_str().unwrap();
+
+        for algo in [HashAlgorithm::Blake3, HashAlgorithm::Xxh3] {
+            assert_eq!(
+                process_hash(path, algo)?,
+                hash_reader(&mut data.as_slice(), algo)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_hash_check() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let good = dir.path().join("good.txt");
+        let bad = dir.path().join("bad\\name.txt");
+        fs::write(&good, "hello")?;
+        fs::write(&bad, "hello")?;
+        let (good, bad) = (good.to_str().unwrap(), bad.to_str().unwrap());
+
+        let hello = encode_digest(
+            &hash_reader(&mut "hello".as_bytes(), HashAlgorithm::Md5)?,
+            HashFormat::Hex,
+        );
+        let sums = format!(
+            "{}\n{}\n\n{}\n",
+            format_sum_line(&hello.to_uppercase(), good),
+            format_sum_line(&"0".repeat(32), bad),
+            format_sum_line(&hello, "no-such-file.txt"),
+        );
+        assert!(sums.starts_with(&format!("{}  ", hello.to_uppercase())));
+        assert!(sums.contains(&format!("\\{}  ", "0".repeat(32))));
+
+        let report = process_hash_check(&mut sums.as_bytes(), HashAlgorithm::Md5, HashFormat::Hex)?;
+        assert_eq!(report.improperly_formatted, 0);
+        let checks = report.checks;
+        let statuses = checks.iter().map(|c| &c.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                &CheckStatus::Ok,
+                &CheckStatus::Failed,
+                &CheckStatus::Unreadable
+            ]
+        );
+        assert_eq!(checks[1].file, bad);
+
+        // 二进制模式的 * 标记
+        let sums = format!("{} *{}\n", hello, good);
+        let report = process_hash_check(&mut sums.as_bytes(), HashAlgorithm::Md5, HashFormat::Hex)?;
+        assert_eq!(report.checks[0].status, CheckStatus::Ok);
+
+        // 用 md5 检查 sha256 的校验文件时是格式错误，不是校验失败
+        let sha256 = encode_digest(
+            &hash_reader(&mut "hello".as_bytes(), HashAlgorithm::Sha256)?,
+            HashFormat::Hex,
+        );
+        let sums = format!("{}\nno-separator\n", format_sum_line(&sha256, good));
+        let report = process_hash_check(&mut sums.as_bytes(), HashAlgorithm::Md5, HashFormat::Hex)?;
+        assert!(report.checks.is_empty());
+        assert_eq!(report.improperly_formatted, 2);
+        let report =
+            process_hash_check(&mut sums.as_bytes(), HashAlgorithm::Sha256, HashFormat::Hex)?;
+        assert_eq!(report.checks[0].status, CheckStatus::Ok);
+        assert_eq!(report.improperly_formatted, 1);
+        Ok(())
+    }
+}
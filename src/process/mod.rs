@@ -1,15 +1,57 @@
 mod b64;
+mod codec;
 mod csv_convert;
+mod csv_ops;
+mod csv_reverse;
+mod csv_stats;
+mod csv_transform;
+mod csv_validate;
+mod encoding;
+mod excel;
 mod gen_pass;
+mod hash;
 mod http_serve;
 mod jwt;
+mod otp;
+mod password_check;
+mod password_policy;
+mod row_writer;
 mod text;
 
-pub use b64::{process_decode, process_encode};
-pub use csv_convert::process_csv;
-pub use gen_pass::process_genpass;
+pub use b64::{
+    base64_engine, detect_base64_format, process_decode, process_encode, shares_crypt_alphabet,
+};
+pub use codec::{process_codec_decode, process_codec_encode};
+pub use csv_convert::{
+    infer_field, process_csv, write_records, ColumnType, CsvReaderConfig, CsvSchema,
+};
+pub use csv_ops::{process_csv_dedup, process_csv_merge, process_csv_sort, SortKey};
+pub use csv_reverse::{flatten_object, process_to_csv, to_cell};
+pub use csv_stats::{
+    process_csv_stats, write_csv_stats, write_csv_stats_table, ColumnStats, TopValue,
+};
+pub use csv_transform::{column_index, compare, CsvProjection, CsvTransform, FilterOp, RowFilter};
+pub use csv_validate::{process_csv_validate, CsvValidator, RowError, ValidationReport};
+pub use encoding::{decode_reader, detect_encoding};
+pub use excel::{process_excel, read_sheet};
+pub use gen_pass::{
+    eff_wordlist, estimate_strength, passphrase_entropy, password_rng, process_genpass,
+    process_genpass_words, GenPassConfig, PasswordRng, PasswordStrength,
+};
+pub use hash::{
+    encode_digest, format_sum_line, hash_reader, process_hash, process_hash_check, CheckStatus,
+    HashCheck, HashCheckReport,
+};
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
+pub use otp::{
+    decode_secret, hotp, otpauth_uri, process_hotp_generate, process_hotp_verify,
+    process_otp_generate, process_otp_new, process_otp_verify, totp, verify_hotp, verify_totp,
+    OtpConfig,
+};
+pub use password_check::{check_password, process_password_check, PasswordReport};
+pub use password_policy::{PasswordPolicy, BUILTIN_POLICIES};
+pub use row_writer::{row_writer, write_value, RowWriter};
 pub use text::{
     process_text_decrypt, process_text_encrypt, process_text_key_generate, process_text_sign,
     process_text_verify,
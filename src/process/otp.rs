@@ -0,0 +1,297 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use data_encoding::BASE32_NOPAD;
+use hmac::{digest::KeyInit, Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::OtpAlgorithm;
+
+// otpauth URI 的 label 和参数中只保留 RFC 3986 的非保留字符
+const URI_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// 一次性密码的参数，默认值和 Google Authenticator 等客户端一致
+#[derive(Debug, Clone, Copy)]
+pub struct OtpConfig {
+    pub digits: u8,
+    // TOTP 的时间步长，单位为秒
+    pub period: u64,
+    pub algorithm: OtpAlgorithm,
+}
+
+impl Default for OtpConfig {
+    fn default() -> Self {
+        Self {
+            digits: 6,
+            period: 30,
+            algorithm: OtpAlgorithm::Sha1,
+        }
+    }
+}
+
+/// 解码 base32 密钥，忽略大小写、空格和末尾的填充
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    let secret = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let key = BASE32_NOPAD
+        .decode(secret.as_bytes())
+        .map_err(|e| anyhow::anyhow!("invalid base32 secret: {}", e))?;
+    if key.is_empty() {
+        anyhow::bail!("secret must not be empty");
+    }
+    Ok(key)
+}
+
+/// RFC 4226 HOTP：对计数器做 HMAC 后动态截断
+pub fn hotp(key: &[u8], counter: u64, config: &OtpConfig) -> String {
+    let msg = counter.to_be_bytes();
+    let digest = match config.algorithm {
+        OtpAlgorithm::Sha1 => hmac_digest::<Hmac<Sha1>>(key, &msg),
+        OtpAlgorithm::Sha256 => hmac_digest::<Hmac<Sha256>>(key, &msg),
+        OtpAlgorithm::Sha512 => hmac_digest::<Hmac<Sha512>>(key, &msg),
+    };
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let bytes = digest[offset..offset + 4].try_into().expect("4 bytes");
+    let code = u32::from_be_bytes(bytes) & 0x7fff_ffff;
+    format!(
+        "{:0width$}",
+        code as u64 % 10u64.pow(config.digits as u32),
+        width = config.digits as usize
+    )
+}
+
+/// RFC 6238 TOTP：以 unix 时间戳所在的时间步作为 HOTP 的计数器
+pub fn totp(key: &[u8], timestamp: u64, config: &OtpConfig) -> String {
+    hotp(key, timestamp / config.period, config)
+}
+
+/// 生成当前时间的验证码，同时返回它的剩余有效秒数
+pub fn process_otp_generate(secret: &str, config: &OtpConfig) -> Result<(String, u64)> {
+    let key = decode_secret(secret)?;
+    let now = unix_time()?;
+    Ok((totp(&key, now, config), config.period - now % config.period))
+}
+
+/// 在当前时间前后 window 个时间步内查找验证码，返回匹配的时间步偏移
+pub fn process_otp_verify(
+    secret: &str,
+    code: &str,
+    window: u64,
+    config: &OtpConfig,
+) -> Result<Option<i64>> {
+    let key = decode_secret(secret)?;
+    Ok(verify_totp(&key, code, unix_time()?, window, config))
+}
+
+pub fn verify_totp(
+    key: &[u8],
+    code: &str,
+    timestamp: u64,
+    window: u64,
+    config: &OtpConfig,
+) -> Option<i64> {
+    let code = code.split_whitespace().collect::<String>();
+    let step = timestamp / config.period;
+    let window = i64::try_from(window).unwrap_or(i64::MAX);
+    // 先检查当前时间步，再向两侧扩展
+    let mut offsets = std::iter::once(0).chain((1..=window).flat_map(|i| [-i, i]));
+    offsets.find(|&offset| {
+        step.checked_add_signed(offset)
+            .is_some_and(|counter| constant_time_eq(&hotp(key, counter, config), &code))
+    })
+}
+
+/// 生成计数器对应的 HOTP 验证码
+pub fn process_hotp_generate(secret: &str, counter: u64, config: &OtpConfig) -> Result<String> {
+    let key = decode_secret(secret)?;
+    Ok(hotp(&key, counter, config))
+}
+
+/// 从 counter 开始向后查找 window 个计数器，返回匹配的计数器，调用方需要保存它加一之后的值
+pub fn process_hotp_verify(
+    secret: &str,
+    code: &str,
+    counter: u64,
+    window: u64,
+    config: &OtpConfig,
+) -> Result<Option<u64>> {
+    let key = decode_secret(secret)?;
+    Ok(verify_hotp(&key, code, counter, window, config))
+}
+
+pub fn verify_hotp(
+    key: &[u8],
+    code: &str,
+    counter: u64,
+    window: u64,
+    config: &OtpConfig,
+) -> Option<u64> {
+    let code = code.split_whitespace().collect::<String>();
+    // HOTP 的计数器只会向前走，只需要向后查找
+    (counter..=counter.saturating_add(window))
+        .find(|&counter| constant_time_eq(&hotp(key, counter, config), &code))
+}
+
+/// 生成新的随机密钥，返回 base32 编码的密钥和 otpauth URI
+pub fn process_otp_new(
+    issuer: &str,
+    account: &str,
+    config: &OtpConfig,
+) -> Result<(String, String)> {
+    if account.is_empty() {
+        anyhow::bail!("account must not be empty");
+    }
+    // 密钥长度和 HMAC 的输出长度一致，也就是 RFC 推荐的长度
+    let mut key = vec![0u8; config.algorithm.key_len()];
+    OsRng.fill_bytes(&mut key);
+    let secret = BASE32_NOPAD.encode(&key);
+    let uri = otpauth_uri(&secret, issuer, account, config);
+    Ok((secret, uri))
+}
+
+/// Key Uri Format: otpauth://totp/Issuer:account?secret=...&issuer=...
+pub fn otpauth_uri(secret: &str, issuer: &str, account: &str, config: &OtpConfig) -> String {
+    let issuer = utf8_percent_encode(issuer, URI_COMPONENT).to_string();
+    let account = utf8_percent_encode(account, URI_COMPONENT);
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+        issuer,
+        account,
+        secret,
+        issuer,
+        config.algorithm.to_string().to_ascii_uppercase(),
+        config.digits,
+        config.period
+    )
+}
+
+fn hmac_digest<M: Mac + KeyInit>(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(msg);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// 比较验证码时不因为第一个不同的字符提前返回
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn unix_time() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_KEY: &[u8] = b"12345678901234567890";
+
+    fn config(digits: u8, algorithm: OtpAlgorithm) -> OtpConfig {
+        OtpConfig {
+            digits,
+            algorithm,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hotp_rfc4226() {
+        let expected = ["755224", "287082", "359152", "969429", "338314"];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SHA1_KEY, counter as u64, &OtpConfig::default()), *code);
+        }
+    }
+
+    #[test]
+    fn test_verify_hotp() -> Result<()> {
+        let config = OtpConfig::default();
+        assert_eq!(verify_hotp(SHA1_KEY, "969429", 3, 0, &config), Some(3));
+        assert_eq!(verify_hotp(SHA1_KEY, "969 429", 1, 2, &config), Some(3));
+        assert_eq!(verify_hotp(SHA1_KEY, "969429", 1, 1, &config), None);
+        // 已经用过的计数器不再接受
+        assert_eq!(verify_hotp(SHA1_KEY, "969429", 4, 10, &config), None);
+        assert_eq!(verify_hotp(SHA1_KEY, "000000", u64::MAX, 10, &config), None);
+
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(process_hotp_generate(secret, 4, &config)?, "338314");
+        assert_eq!(
+            process_hotp_verify(secret, "338314", 0, 5, &config)?,
+            Some(4)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_totp_rfc6238() {
+        let sha256_key = b"12345678901234567890123456789012";
+        let sha512_key = b"1234567890123456789012345678901234567890123456789012345678901234";
+        let cases: [(&[u8], OtpAlgorithm, u64, &str); 5] = [
+            (SHA1_KEY, OtpAlgorithm::Sha1, 59, "94287082"),
+            (SHA1_KEY, OtpAlgorithm::Sha1, 1111111109, "07081804"),
+            (sha256_key, OtpAlgorithm::Sha256, 59, "46119246"),
+            (sha512_key, OtpAlgorithm::Sha512, 59, "90693936"),
+            (sha512_key, OtpAlgorithm::Sha512, 20000000000, "47863826"),
+        ];
+        for (key, algorithm, time, code) in cases {
+            assert_eq!(totp(key, time, &config(8, algorithm)), code);
+        }
+    }
+
+    #[test]
+    fn test_verify_totp() -> Result<()> {
+        let config = OtpConfig::default();
+        let key = decode_secret("gezd gnbv gy3t qojq gezd gnbv gy3t qojq")?;
+        assert_eq!(key, SHA1_KEY);
+
+        let code = totp(&key, 1111111109, &config);
+        assert_eq!(verify_totp(&key, &code, 1111111109, 0, &config), Some(0));
+        assert_eq!(verify_totp(&key, &code, 1111111139, 1, &config), Some(-1));
+        assert_eq!(verify_totp(&key, &code, 1111111139, 0, &config), None);
+        assert_eq!(verify_totp(&key, "000000", 1111111109, 1, &config), None);
+
+        // window 很大时不能溢出，也不能预先分配所有偏移
+        assert_eq!(
+            verify_totp(&key, &code, 1111111139, u64::MAX, &config),
+            Some(-1)
+        );
+
+        assert!(decode_secret("not base32!").is_err());
+        assert!(decode_secret("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_otp_new() -> Result<()> {
+        let config = config(6, OtpAlgorithm::Sha256);
+        let (secret, uri) = process_otp_new("ACME Co", "alice@example.com", &config)?;
+        assert_eq!(decode_secret(&secret)?.len(), 32);
+        assert_eq!(
+            uri,
+            format!(
+                "otpauth://totp/ACME%20Co:alice%40example.com?secret={}&issuer=ACME%20Co&algorithm=SHA256&digits=6&period=30",
+                secret
+            )
+        );
+
+        let (code, remaining) = process_otp_generate(&secret, &config)?;
+        assert_eq!(code.len(), 6);
+        assert!((1..=30).contains(&remaining));
+        assert!(process_otp_verify(&secret, &code, 1, &config)?.is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::{BufRead, BufReader, Read};
+
+use serde::Serialize;
+use zxcvbn::{matching::patterns::MatchPattern, zxcvbn};
+
+/// zxcvbn 对单个密码的评估结果
+#[derive(Debug, Serialize)]
+pub struct PasswordReport {
+    pub password: String,
+    pub score: u8,
+    pub guesses: u64,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+    // 形如 dictionary(password)、sequence(abc) 的匹配模式
+    pub patterns: Vec<String>,
+}
+
+pub fn check_password(password: &str) -> PasswordReport {
+    let estimate = zxcvbn(password, &[]);
+    let feedback = estimate.feedback();
+    let patterns = estimate
+        .sequence()
+        .iter()
+        .map(|m| {
+            let name = match m.pattern {
+                MatchPattern::Dictionary(_) => "dictionary",
+                MatchPattern::Spatial(_) => "spatial",
+                MatchPattern::Repeat(_) => "repeat",
+                MatchPattern::Sequence(_) => "sequence",
+                MatchPattern::Regex(_) => "regex",
+                MatchPattern::Date(_) => "date",
+                MatchPattern::BruteForce => "bruteforce",
+            };
+            format!("{}({})", name, m.token)
+        })
+        .collect();
+
+    PasswordReport {
+        password: password.to_string(),
+        score: estimate.score().into(),
+        guesses: estimate.guesses(),
+        warning: feedback.and_then(|f| f.warning()).map(|w| w.to_string()),
+        suggestions: feedback
+            .map(|f| f.suggestions().iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+        patterns,
+    }
+}
+
+/// 每行一个密码，忽略空行
+pub fn process_password_check(reader: &mut dyn Read) -> anyhow::Result<Vec<PasswordReport>> {
+    let mut reports = Vec::new();
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        let password = line.trim_end_matches('\r');
+        if !password.is_empty() {
+            reports.push(check_password(password));
+        }
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_password_check() -> anyhow::Result<()> {
+        let data = "password\r\n\nabcdefgh\naaaaaaaa\nrK8#vQ2!zL5@\n";
+        let reports = process_password_check(&mut data.as_bytes())?;
+        assert_eq!(reports.len(), 4);
+
+        assert_eq!(reports[0].score, 0);
+        assert_eq!(reports[0].patterns, ["dictionary(password)"]);
+        assert!(reports[0].warning.is_some());
+        assert!(!reports[0].suggestions.is_empty());
+
+        assert_eq!(reports[1].patterns, ["sequence(abcdefgh)"]);
+        assert_eq!(reports[2].patterns, ["repeat(aaaaaaaa)"]);
+        assert_eq!(reports[3].score, 4);
+        assert!(reports[3].warning.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::GenPassConfig;
+
+/// 密码策略，可以从 yaml 文件加载，未指定的规则沿用命令行参数
+///
+/// ```yaml
+/// min_length: 12
+/// max_length: 20
+/// symbol: true
+/// exclude: "$"
+/// start_with_letter: true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    // 没有指定 --length 时生成的长度
+    pub length: Option<u8>,
+    pub min_length: Option<u8>,
+    pub max_length: Option<u8>,
+    pub uppercase: Option<bool>,
+    pub lowercase: Option<bool>,
+    pub number: Option<bool>,
+    pub symbol: Option<bool>,
+    pub charset: Option<String>,
+    // 不允许出现的字符
+    pub exclude: String,
+    pub min_digits: u8,
+    pub min_symbols: u8,
+    pub start_with_letter: bool,
+}
+
+type CharTest = fn(&char) -> bool;
+
+// 没有指定长度时，在策略允许的范围内尽量接近这个长度
+const DEFAULT_LENGTH: u8 = 16;
+
+impl PasswordPolicy {
+    /// name 为内置策略的名称或者 yaml 文件路径
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        match Self::builtin(name) {
+            Some(policy) => Ok(policy),
+            None => {
+                let content = fs::read_to_string(name).map_err(|e| {
+                    anyhow::anyhow!(
+                        "{} is neither a built-in policy ({}) nor a readable file: {}",
+                        name,
+                        BUILTIN_POLICIES.join(", "),
+                        e
+                    )
+                })?;
+                let policy: Self = serde_yaml::from_str(&content)?;
+                policy.check_bounds()?;
+                Ok(policy)
+            }
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let policy = match name {
+            // AWS IAM 密码策略：8 到 128 位，包含大小写字母、数字和符号
+            "aws-iam" => Self {
+                length: Some(20),
+                min_length: Some(8),
+                max_length: Some(128),
+                uppercase: Some(true),
+                lowercase: Some(true),
+                number: Some(true),
+                symbol: Some(true),
+                ..Default::default()
+            },
+            // MySQL validate_password MEDIUM 策略，并去掉 sql 和 shell 中需要转义的字符
+            "mysql" => Self {
+                length: Some(20),
+                min_length: Some(8),
+                max_length: Some(32),
+                uppercase: Some(true),
+                lowercase: Some(true),
+                number: Some(true),
+                symbol: Some(true),
+                exclude: "'\"\\`$".to_string(),
+                ..Default::default()
+            },
+            "pin" => Self {
+                length: Some(6),
+                min_length: Some(4),
+                max_length: Some(8),
+                charset: Some("0123456789".to_string()),
+                ..Default::default()
+            },
+            _ => return None,
+        };
+        Some(policy)
+    }
+
+    /// 指定了 length 时直接使用，否则取最接近 DEFAULT_LENGTH 的合法长度
+    pub fn default_length(&self) -> u8 {
+        let (min, max) = self.bounds();
+        self.length
+            .unwrap_or_else(|| DEFAULT_LENGTH.min(max).max(min))
+    }
+
+    // 未指定的边界取默认值：最短 1 位，最长 255 位
+    fn bounds(&self) -> (u8, u8) {
+        (
+            self.min_length.unwrap_or(1),
+            self.max_length.unwrap_or(u8::MAX),
+        )
+    }
+
+    // 按默认值补齐边界后再检查，只写了一个边界的策略也可能互相矛盾
+    fn check_bounds(&self) -> anyhow::Result<()> {
+        let (min, max) = self.bounds();
+        if min > max {
+            anyhow::bail!("min_length {} is greater than max_length {}", min, max);
+        }
+        if let Some(length) = self.length.filter(|l| !(min..=max).contains(l)) {
+            anyhow::bail!(
+                "length {} is outside the policy range {}..={}",
+                length,
+                min,
+                max
+            );
+        }
+        Ok(())
+    }
+
+    /// 将策略合并到生成配置中，长度不符合策略时直接报错
+    pub fn apply(&self, mut config: GenPassConfig) -> anyhow::Result<GenPassConfig> {
+        if let Some(min) = self.min_length.filter(|&min| config.length < min) {
+            anyhow::bail!(
+                "password length {} is shorter than the policy minimum {}",
+                config.length,
+                min
+            );
+        }
+        if let Some(max) = self.max_length.filter(|&max| config.length > max) {
+            anyhow::bail!(
+                "password length {} exceeds the policy maximum {}",
+                config.length,
+                max
+            );
+        }
+
+        config.uppercase = self.uppercase.unwrap_or(config.uppercase);
+        config.lowercase = self.lowercase.unwrap_or(config.lowercase);
+        config.number = self.number.unwrap_or(config.number);
+        config.symbol = self.symbol.unwrap_or(config.symbol);
+        if self.charset.is_some() {
+            config.charset = self.charset.clone();
+        }
+        config.exclude.push_str(&self.exclude);
+        config.min_digits = config.min_digits.max(self.min_digits);
+        config.min_symbols = config.min_symbols.max(self.min_symbols);
+        config.start_with_letter |= self.start_with_letter;
+        Ok(config)
+    }
+
+    /// 检查密码是否满足策略，返回所有不满足的规则
+    pub fn validate(&self, password: &str) -> anyhow::Result<()> {
+        let length = password.chars().count();
+        let count = |f: CharTest| password.chars().filter(f).count();
+        let mut violations = Vec::new();
+
+        if let Some(min) = self.min_length.filter(|&min| length < min as usize) {
+            violations.push(format!("shorter than {} characters", min));
+        }
+        if let Some(max) = self.max_length.filter(|&max| length > max as usize) {
+            violations.push(format!("longer than {} characters", max));
+        }
+        if let Some(c) = password.chars().find(|&c| self.exclude.contains(c)) {
+            violations.push(format!("contains forbidden character {:?}", c));
+        }
+        if let Some(charset) = &self.charset {
+            if let Some(c) = password.chars().find(|&c| !charset.contains(c)) {
+                violations.push(format!("contains {:?} which is not in the charset", c));
+            }
+        }
+
+        let classes: [(&str, Option<bool>, CharTest); 4] = [
+            ("an uppercase letter", self.uppercase, |c| c.is_uppercase()),
+            ("a lowercase letter", self.lowercase, |c| c.is_lowercase()),
+            ("a digit", self.number, char::is_ascii_digit),
+            ("a symbol", self.symbol, |c| !c.is_alphanumeric()),
+        ];
+        for (name, required, f) in classes {
+            if required == Some(true) && count(f) == 0 {
+                violations.push(format!("does not contain {}", name));
+            }
+        }
+        if count(char::is_ascii_digit) < self.min_digits as usize {
+            violations.push(format!("contains fewer than {} digits", self.min_digits));
+        }
+        if count(|c| !c.is_alphanumeric()) < self.min_symbols as usize {
+            violations.push(format!("contains fewer than {} symbols", self.min_symbols));
+        }
+        if self.start_with_letter && !password.starts_with(char::is_alphabetic) {
+            violations.push("does not start with a letter".to_string());
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!(
+                "password violates the policy: {}",
+                violations.join(", ")
+            )),
+        }
+    }
+}
+
+pub const BUILTIN_POLICIES: &[&str] = &["aws-iam", "mysql", "pin"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_genpass;
+    use rand::rngs::OsRng;
+
+    fn generate(policy: &PasswordPolicy, length: Option<u8>) -> anyhow::Result<String> {
+        let config = GenPassConfig {
+            length: length.unwrap_or_else(|| policy.default_length()),
+            ..Default::default()
+        };
+        let password = process_genpass(&policy.apply(config)?, &mut OsRng)?;
+        policy.validate(&password)?;
+        Ok(password)
+    }
+
+    #[test]
+    fn test_builtin_policies() -> anyhow::Result<()> {
+        for name in BUILTIN_POLICIES {
+            let policy = PasswordPolicy::load(name)?;
+            for _ in 0..50 {
+                generate(&policy, None)?;
+            }
+        }
+
+        let pin = generate(&PasswordPolicy::load("pin")?, None)?;
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        assert!(generate(&PasswordPolicy::load("pin")?, Some(16)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_file() -> anyhow::Result<()> {
+        let policy: PasswordPolicy = serde_yaml::from_str(
+            "max_length: 20\nnumber: true\nsymbol: true\nexclude: $\nstart_with_letter: true\nmin_digits: 6\n",
+        )?;
+        assert_eq!(policy.default_length(), 16);
+        for _ in 0..50 {
+            let password = generate(&policy, Some(10))?;
+            assert!(password.starts_with(char::is_alphabetic));
+            assert!(!password.contains('$'));
+        }
+        assert!(generate(&policy, Some(21)).is_err());
+
+        let err = policy.validate("1$abc").unwrap_err().to_string();
+        assert!(err.contains("forbidden character '$'"));
+        assert!(err.contains("does not start with a letter"));
+        assert!(err.contains("fewer than 6 digits"));
+
+        assert!(serde_yaml::from_str::<PasswordPolicy>("max_len: 20").is_err());
+        assert!(PasswordPolicy::load("no-such-policy.yaml").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_bounds() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let load = |content: &str| {
+            let path = dir.path().join("policy.yaml");
+            fs::write(&path, content)?;
+            PasswordPolicy::load(path.to_str().unwrap())
+        };
+
+        // 只写了一个边界时，另一个边界取默认值
+        assert_eq!(load("max_length: 10\n")?.default_length(), 10);
+        assert_eq!(load("min_length: 20\n")?.default_length(), 20);
+        let err = load("max_length: 0\n").unwrap_err().to_string();
+        assert!(err.contains("min_length 1 is greater than max_length 0"));
+
+        let err = load("length: 30\nmax_length: 20\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("length 30 is outside the policy range 1..=20"));
+        assert!(load("length: 4\nmin_length: 8\n").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,597 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use parquet::{
+    basic::{Compression, LogicalType, Repetition, Type as PhysicalType},
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use serde_json::{Map, Value};
+
+use crate::{cli::OutputFormat, to_cell};
+
+// parquet 每个 row group 的行数，也是写出时在内存中缓存的行数
+const PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+
+/// 逐行写出转换后的记录，内存占用只和单行大小有关
+pub trait RowWriter {
+    fn write_row(&mut self, row: &Value) -> Result<()>;
+    fn finish(&mut self) -> Result<()>;
+}
+
+pub fn row_writer<'a, W: Write + 'a>(format: OutputFormat, writer: W) -> Box<dyn RowWriter + 'a> {
+    match format {
+        OutputFormat::Json => Box::new(JsonArrayWriter::new(writer)),
+        OutputFormat::Yaml => Box::new(YamlSeqWriter::new(writer)),
+        OutputFormat::Toml => Box::new(TomlWriter::new(writer)),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { writer }),
+        OutputFormat::MessagePack => Box::new(MsgpackWriter::new(writer)),
+        OutputFormat::Parquet => Box::new(ParquetWriter::new(writer)),
+    }
+}
+
+/// 将一个完整的值（如分组后的结果）按指定格式写出
+pub fn write_value(format: OutputFormat, mut writer: impl Write, value: &Value) -> Result<()> {
+    match format {
+        OutputFormat::Parquet => {
+            let Value::Array(rows) = value else {
+                anyhow::bail!("parquet output only supports a list of rows");
+            };
+            let mut writer = row_writer(format, &mut writer);
+            for row in rows {
+                writer.write_row(row)?;
+            }
+            return writer.finish();
+        }
+        OutputFormat::Json => serde_json::to_writer_pretty(&mut writer, value)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut writer, value)?,
+        OutputFormat::Toml => writer.write_all(toml::to_string(&strip_null(value))?.as_bytes())?,
+        OutputFormat::Ndjson => {
+            serde_json::to_writer(&mut writer, value)?;
+            writer.write_all(b"\n")?;
+        }
+        OutputFormat::MessagePack => rmp_serde::encode::write_named(&mut writer, value)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 输出和 serde_json::to_string_pretty(&Vec<Value>) 相同格式的 json 数组
+struct JsonArrayWriter<W> {
+    writer: W,
+    count: usize,
+}
+
+/// 输出和 serde_yaml::to_string(&Vec<Value>) 相同格式的 yaml 序列
+struct YamlSeqWriter<W> {
+    writer: W,
+    count: usize,
+}
+
+/// toml 的顶层必须是 table，每条记录写成一个 [[rows]]
+struct TomlWriter<W> {
+    writer: W,
+    count: usize,
+}
+
+struct NdjsonWriter<W> {
+    writer: W,
+}
+
+/// msgpack 数组需要预先知道长度，行先写入临时文件，结束时写出数组头再复制过去
+struct MsgpackWriter<W> {
+    writer: W,
+    spill: Option<BufWriter<File>>,
+    count: u32,
+}
+
+/// parquet 需要预先确定 schema，行先写入临时文件并同时推断每列的类型，
+/// 所有行的类型确定后再按 row group 写出，后面的行出现浮点数或新的列时 schema 会相应放宽
+struct ParquetWriter<W> {
+    writer: W,
+    spill: Option<BufWriter<File>>,
+    columns: Vec<(String, Option<ParquetColumn>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParquetColumn {
+    Bool,
+    Int,
+    Float,
+    String,
+}
+
+impl<W: Write> JsonArrayWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> YamlSeqWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> TomlWriter<W> {
+    fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+}
+
+impl<W: Write> RowWriter for JsonArrayWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        let sep = if self.count == 0 { "[\n" } else { ",\n" };
+        self.writer.write_all(sep.as_bytes())?;
+
+        // json 字符串中的换行会被转义，可以安全地按行缩进
+        let content = serde_json::to_string_pretty(row)?;
+        for (i, line) in content.lines().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b"\n")?;
+            }
+            write!(self.writer, "  {}", line)?;
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let end = if self.count == 0 { "[]" } else { "\n]" };
+        self.writer.write_all(end.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowWriter for YamlSeqWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        let content = serde_yaml::to_string(row)?;
+        for (i, line) in content.lines().enumerate() {
+            let prefix = if i == 0 { "- " } else { "  " };
+            match line.is_empty() {
+                true => writeln!(self.writer)?,
+                false => writeln!(self.writer, "{}{}", prefix, line)?,
+            }
+        }
+
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"[]\n")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowWriter for TomlWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        if self.count > 0 {
+            self.writer.write_all(b"\n")?;
+        }
+
+        let mut table = serde_json::Map::new();
+        table.insert("rows".to_string(), Value::Array(vec![strip_null(row)]));
+        self.writer.write_all(toml::to_string(&table)?.as_bytes())?;
+
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.count == 0 {
+            self.writer.write_all(b"rows = []\n")?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowWriter for NdjsonWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, row)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> MsgpackWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            spill: None,
+            count: 0,
+        }
+    }
+}
+
+impl<W: Write> RowWriter for MsgpackWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        let spill = match &mut self.spill {
+            Some(spill) => spill,
+            None => self.spill.insert(BufWriter::new(tempfile::tempfile()?)),
+        };
+        rmp_serde::encode::write_named(spill, row)?;
+        self.count = self
+            .count
+            .checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("too many rows for a msgpack array"))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        rmp::encode::write_array_len(&mut self.writer, self.count)?;
+        if let Some(spill) = self.spill.take() {
+            let mut spill = spill.into_inner().map_err(|e| e.into_error())?;
+            spill.seek(SeekFrom::Start(0))?;
+            io::copy(&mut spill, &mut self.writer)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ParquetWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            spill: None,
+            columns: Vec::new(),
+        }
+    }
+
+    fn create_file(&self) -> Result<SerializedFileWriter<Vec<u8>>> {
+        let fields = self
+            .columns
+            .iter()
+            .map(|(name, column)| {
+                let column = column.unwrap_or(ParquetColumn::String);
+                let physical = match column {
+                    ParquetColumn::Bool => PhysicalType::BOOLEAN,
+                    ParquetColumn::Int => PhysicalType::INT64,
+                    ParquetColumn::Float => PhysicalType::DOUBLE,
+                    ParquetColumn::String => PhysicalType::BYTE_ARRAY,
+                };
+                let logical = (column == ParquetColumn::String).then_some(LogicalType::String);
+                Ok(Arc::new(
+                    Type::primitive_type_builder(name, physical)
+                        .with_repetition(Repetition::OPTIONAL)
+                        .with_logical_type(logical)
+                        .build()?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        Ok(SerializedFileWriter::new(
+            Vec::new(),
+            Arc::new(schema),
+            Arc::new(props),
+        )?)
+    }
+
+    // 写入内存后在每个 row group 结束时转写到 writer，SerializedFileWriter 要求 Send
+    fn write_row_group(
+        &mut self,
+        file: &mut SerializedFileWriter<Vec<u8>>,
+        rows: &[Map<String, Value>],
+    ) -> Result<()> {
+        let mut group = file.next_row_group()?;
+        for (name, kind) in self.columns.iter() {
+            let mut column = group
+                .next_column()?
+                .ok_or_else(|| anyhow::anyhow!("missing parquet column \"{}\"", name))?;
+            let values = rows
+                .iter()
+                .filter_map(|row| row.get(name).filter(|v| !v.is_null()));
+            let levels = rows
+                .iter()
+                .map(|row| row.get(name).is_some_and(|v| !v.is_null()) as i16)
+                .collect::<Vec<_>>();
+            let mismatch =
+                |v: &Value| anyhow::anyhow!("column \"{}\": unexpected value {}", name, v);
+
+            match kind.unwrap_or(ParquetColumn::String) {
+                ParquetColumn::Bool => {
+                    let data = values
+                        .map(|v| v.as_bool().ok_or_else(|| mismatch(v)))
+                        .collect::<Result<Vec<_>>>()?;
+                    column
+                        .typed::<BoolType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ParquetColumn::Int => {
+                    let data = values
+                        .map(|v| v.as_i64().ok_or_else(|| mismatch(v)))
+                        .collect::<Result<Vec<_>>>()?;
+                    column
+                        .typed::<Int64Type>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ParquetColumn::Float => {
+                    let data = values
+                        .map(|v| v.as_f64().ok_or_else(|| mismatch(v)))
+                        .collect::<Result<Vec<_>>>()?;
+                    column
+                        .typed::<DoubleType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+                ParquetColumn::String => {
+                    let data = values
+                        .map(|v| ByteArray::from(to_cell(v.clone()).into_bytes()))
+                        .collect::<Vec<_>>();
+                    column
+                        .typed::<ByteArrayType>()
+                        .write_batch(&data, Some(&levels), None)?;
+                }
+            }
+            column.close()?;
+        }
+        group.close()?;
+
+        // 已经写入的字节不会再被修改，可以从缓冲区中取出
+        file.flush()?;
+        self.writer.write_all(&std::mem::take(file.inner_mut()))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowWriter for ParquetWriter<W> {
+    fn write_row(&mut self, row: &Value) -> Result<()> {
+        let Value::Object(row) = row else {
+            anyhow::bail!("parquet rows must be objects");
+        };
+        for (key, value) in row {
+            let idx = match self.columns.iter().position(|(c, _)| c == key) {
+                Some(idx) => idx,
+                None => {
+                    self.columns.push((key.clone(), None));
+                    self.columns.len() - 1
+                }
+            };
+            self.columns[idx].1 = merge_parquet_column(self.columns[idx].1, value);
+        }
+
+        let spill = match &mut self.spill {
+            Some(spill) => spill,
+            None => self.spill.insert(BufWriter::new(tempfile::tempfile()?)),
+        };
+        serde_json::to_writer(&mut *spill, row)?;
+        spill.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut file = self.create_file()?;
+        if let Some(spill) = self.spill.take() {
+            let mut spill = spill.into_inner().map_err(|e| e.into_error())?;
+            spill.seek(SeekFrom::Start(0))?;
+
+            let mut rows = Vec::with_capacity(PARQUET_ROW_GROUP_SIZE);
+            for row in serde_json::Deserializer::from_reader(BufReader::new(spill)).into_iter() {
+                rows.push(row?);
+                if rows.len() >= PARQUET_ROW_GROUP_SIZE {
+                    self.write_row_group(&mut file, &rows)?;
+                    rows.clear();
+                }
+            }
+            if !rows.is_empty() {
+                self.write_row_group(&mut file, &rows)?;
+            }
+        }
+        self.writer.write_all(&file.into_inner()?)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 所有非 null 值都是同一类型时使用该类型，整数和浮点数混合时为浮点数，否则为字符串
+fn merge_parquet_column(current: Option<ParquetColumn>, value: &Value) -> Option<ParquetColumn> {
+    let kind = match value {
+        Value::Null => return current,
+        Value::Bool(_) => ParquetColumn::Bool,
+        Value::Number(n) if n.is_i64() => ParquetColumn::Int,
+        Value::Number(_) => ParquetColumn::Float,
+        _ => ParquetColumn::String,
+    };
+    let merged = match (current, kind) {
+        (None, kind) => kind,
+        (Some(a), b) if a == b => a,
+        (Some(ParquetColumn::Int), ParquetColumn::Float)
+        | (Some(ParquetColumn::Float), ParquetColumn::Int) => ParquetColumn::Float,
+        _ => ParquetColumn::String,
+    };
+    Some(merged)
+}
+
+// toml 不支持 null，去掉 null 字段
+fn strip_null(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k.clone(), strip_null(v)))
+            .collect(),
+        Value::Array(arr) => arr
+            .iter()
+            .filter(|v| !v.is_null())
+            .map(strip_null)
+            .collect(),
+        v => v.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<Value> {
+        vec![
+            json!({"Name": "Buffon", "Kit Number": 77, "Nickname": null}),
+            json!({"Name": "Chiellini", "Kit Number": 3, "Tags": ["captain", "defender"]}),
+        ]
+    }
+
+    fn write_all(format: OutputFormat, rows: &[Value]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut writer = row_writer(format, &mut buf);
+        for row in rows {
+            writer.write_row(row)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_json_array_writer() -> Result<()> {
+        let rows = rows();
+        let content = write_all(OutputFormat::Json, &rows)?;
+        assert_eq!(content, serde_json::to_string_pretty(&rows)?.as_bytes());
+
+        let content = write_all(OutputFormat::Json, &[])?;
+        assert_eq!(content, b"[]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_seq_writer() -> Result<()> {
+        let rows = rows();
+        let content = write_all(OutputFormat::Yaml, &rows)?;
+        assert_eq!(content, serde_yaml::to_string(&rows)?.as_bytes());
+
+        let content = write_all(OutputFormat::Yaml, &[])?;
+        assert_eq!(
+            content,
+            serde_yaml::to_string(&Vec::<Value>::new())?.as_bytes()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_writer() -> Result<()> {
+        let content = write_all(OutputFormat::Toml, &rows())?;
+        let table: toml::Table = String::from_utf8(content)?.parse()?;
+        let rows = table["rows"].as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["Kit Number"].as_integer(), Some(77));
+        assert!(rows[0].get("Nickname").is_none());
+        assert_eq!(rows[1]["Tags"].as_array().unwrap().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_writer() -> Result<()> {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let mut rows = rows();
+        rows.push(json!({"Name": "Bonucci", "Kit Number": 19.5, "Nickname": "Leo"}));
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&write_all(OutputFormat::Parquet, &rows)?)?;
+
+        let reader = SerializedFileReader::new(file)?;
+        let values = reader
+            .get_row_iter(None)?
+            .map(|row| Ok(row?.to_string()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            values,
+            [
+                r#"{Name: "Buffon", Kit Number: 77.0, Nickname: null, Tags: null}"#,
+                r#"{Name: "Chiellini", Kit Number: 3.0, Nickname: null, Tags: "["captain","defender"]"}"#,
+                r#"{Name: "Bonucci", Kit Number: 19.5, Nickname: "Leo", Tags: null}"#,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parquet_writer_row_groups() -> Result<()> {
+        use parquet::{
+            file::reader::{FileReader, SerializedFileReader},
+            record::RowAccessor,
+        };
+
+        // 超过一个 row group 后才出现浮点数和新的列，schema 需要放宽
+        let total = PARQUET_ROW_GROUP_SIZE * 2 + 5;
+        let rows = (0..total)
+            .map(|i| match i {
+                i if i == total - 1 => json!({"id": i, "score": 0.5, "note": "last"}),
+                i => json!({"id": i, "score": i}),
+            })
+            .collect::<Vec<_>>();
+        let mut file = tempfile::tempfile()?;
+        file.write_all(&write_all(OutputFormat::Parquet, &rows)?)?;
+
+        let reader = SerializedFileReader::new(file)?;
+        assert_eq!(reader.metadata().num_row_groups(), 3);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), total as i64);
+        let rows = reader.get_row_iter(None)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(rows.len(), total);
+        for (i, row) in rows.iter().enumerate().take(total - 1) {
+            assert_eq!(row.get_long(0)?, i as i64);
+            assert_eq!(row.get_double(1)?, i as f64);
+        }
+        let last = &rows[total - 1];
+        assert_eq!(last.get_double(1)?, 0.5);
+        assert_eq!(last.get_string(2)?, "last");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_value() -> Result<()> {
+        let value = json!({"Goalkeeper": rows()});
+        let mut buf = Vec::new();
+        write_value(OutputFormat::Toml, &mut buf, &value)?;
+        let table: toml::Table = String::from_utf8(buf)?.parse()?;
+        assert_eq!(table["Goalkeeper"].as_array().unwrap().len(), 2);
+
+        let mut buf = Vec::new();
+        write_value(OutputFormat::Yaml, &mut buf, &value)?;
+        assert_eq!(serde_yaml::from_slice::<Value>(&buf)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ndjson_msgpack_writer() -> Result<()> {
+        let rows = rows();
+        let content = write_all(OutputFormat::Ndjson, &rows)?;
+        let lines = String::from_utf8(content)?;
+        let decoded = lines
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Value>, _>>()?;
+        assert_eq!(decoded, rows);
+
+        // 和直接序列化 Vec<Value> 的结果相同
+        let content = write_all(OutputFormat::MessagePack, &rows)?;
+        let mut expected = Vec::new();
+        rmp_serde::encode::write_named(&mut expected, &rows)?;
+        assert_eq!(content, expected);
+        assert_eq!(rmp_serde::from_slice::<Vec<Value>>(&content)?, rows);
+
+        let content = write_all(OutputFormat::MessagePack, &[])?;
+        assert!(rmp_serde::from_slice::<Vec<Value>>(&content)?.is_empty());
+        Ok(())
+    }
+}
@@ -1,9 +1,8 @@
-use crate::{process_genpass, Base64Format, TextSignFormat};
-use anyhow::{Ok, Result};
-use base64::{
-    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
-    Engine,
+use crate::{
+    base64_engine, process_decode, process_genpass, Base64Format, GenPassConfig, TextSignFormat,
 };
+use anyhow::{Ok, Result};
+use base64::Engine;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use std::{collections::HashMap, io::Read};
@@ -99,10 +98,7 @@ impl TextEncrypt for ChaCha20 {
         let ciphertext = cipher.encrypt(Nonce::from_slice(&self.nonce), buf.as_ref());
 
         match ciphertext.is_ok() {
-            true => match format {
-                Base64Format::Standard => Ok(STANDARD.encode(ciphertext.unwrap())),
-                Base64Format::UrlSafe => Ok(URL_SAFE_NO_PAD.encode(ciphertext.unwrap())),
-            },
+            true => Ok(base64_engine(format)?.encode(ciphertext.unwrap())),
             false => Err(anyhow::anyhow!("encryptor failed")),
         }
     }
@@ -117,10 +113,9 @@ impl TextDecrypt for ChaCha20 {
         let mut buf = Vec::new();
         reader.read_to_end(&mut buf)?;
 
-        let ciphertext_decode = match format {
-            Base64Format::Standard => STANDARD.decode(&buf),
-            Base64Format::UrlSafe => URL_SAFE_NO_PAD.decode(&buf),
-        };
+        let mut decoded = Vec::new();
+        let ciphertext_decode =
+            process_decode(&mut buf.as_slice(), &mut decoded, format).map(|_| decoded);
 
         let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.key));
 
@@ -155,11 +150,14 @@ impl Blake3 {
 
     fn generate() -> Result<HashMap<&'static str, Vec<u8>>> {
         let key = process_genpass(
-            32,
-            "true".to_string(),
-            "true".to_string(),
-            "true".to_string(),
-            "true".to_string(),
+            &GenPassConfig {
+                length: 32,
+                number: true,
+                symbol: true,
+                ..Default::default()
+            },
+            // 密钥只使用操作系统的随机数，不受 --seed 影响
+            &mut OsRng,
         )?;
         let mut map = HashMap::new();
         map.insert("blake3.txt", key.as_bytes().to_vec());
@@ -317,6 +315,12 @@ mod tests {
         let decrypt_str = process_text_decrypt(&mut encrypt_str, key, nonce, format)?;
 
         assert_eq!(decrypt_str, "hello world!");
+
+        let mut reader = "hello world!".as_bytes();
+        let encrypt_str = process_text_encrypt(&mut reader, key, nonce, Base64Format::UrlSafe)?;
+        let decrypt_str =
+            process_text_decrypt(&mut encrypt_str.as_bytes(), key, nonce, Base64Format::Auto)?;
+        assert_eq!(decrypt_str, "hello world!");
         Ok(())
     }
 
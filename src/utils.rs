@@ -1,5 +1,8 @@
 use anyhow::Result;
-use std::{fs::File, io::Read};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = match input {
@@ -9,6 +12,14 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     Ok(reader)
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = match output {
+        "-" => Box::new(BufWriter::new(std::io::stdout())),
+        _ => Box::new(BufWriter::new(File::create(output)?)),
+    };
+    Ok(writer)
+}
+
 pub fn get_content(input: &str) -> Result<Vec<u8>> {
     let mut reader = get_reader(input)?;
     let mut buf = Vec::new();
@@ -31,4 +42,16 @@ mod tests {
         let mut result = get_reader("fixtures/hello_world.txt").unwrap();
         assert_eq!(result.read_to_end(&mut Vec::new()).unwrap(), 12);
     }
+
+    #[test]
+    fn test_get_writer() -> Result<()> {
+        let output = std::env::temp_dir().join("rcli_test_get_writer.txt");
+        let output = output.to_str().unwrap();
+        let mut writer = get_writer(output)?;
+        writer.write_all(b"hello world")?;
+        drop(writer);
+        assert_eq!(get_content(output)?, b"hello world");
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
 }